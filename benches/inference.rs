@@ -0,0 +1,93 @@
+//! Brain inference for a full population, run with `cargo bench --bench inference`
+
+use std::time::{Duration, Instant};
+
+use ava::{
+    nn::{FlatNet, Net},
+    sim_config::SimConfig,
+    NUM_CELLS,
+};
+use bevy::tasks::{ComputeTaskPool, ParallelSliceMut, TaskPool};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+const NUM_ROUNDS: u32 = 50;
+
+struct Brain {
+    net: Net,
+    flat: FlatNet,
+    inputs: Vec<f64>,
+    values: Vec<f32>,
+}
+
+fn main() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let config = SimConfig::default();
+    let layer_sizes = config.net_arch();
+    let activations = config.layer_activations();
+    let mut brains: Vec<Brain> = (0..NUM_CELLS)
+        .map(|_| {
+            let net = Net::new(layer_sizes.clone(), &activations, &mut rng);
+            let flat = net.to_flat();
+            Brain {
+                inputs: (0..layer_sizes[0])
+                    .map(|_| rng.gen_range(0.0..1.0))
+                    .collect(),
+                values: vec![0.0; flat.num_values()],
+                net,
+                flat,
+            }
+        })
+        .collect();
+
+    let predict = time(|| {
+        for b in brains.iter() {
+            std::hint::black_box(b.net.predict(&b.inputs));
+        }
+    });
+    let flat = time(|| {
+        for b in brains.iter_mut() {
+            b.flat.predict_into(&b.inputs, &mut b.values);
+        }
+        std::hint::black_box(&brains);
+    });
+    let pool = ComputeTaskPool::init(TaskPool::default);
+    let parallel = time(|| {
+        brains.par_splat_map_mut(pool, None, |chunk| {
+            for b in chunk.iter_mut() {
+                b.flat.predict_into(&b.inputs, &mut b.values);
+            }
+        });
+        std::hint::black_box(&brains);
+    });
+
+    println!(
+        "{} brains of {:?}, mean of {} rounds",
+        NUM_CELLS, layer_sizes, NUM_ROUNDS
+    );
+    report("Net::predict", predict, predict);
+    report("FlatNet::predict_into", flat, predict);
+    report(
+        &format!("parallel, {} threads", pool.thread_num()),
+        parallel,
+        predict,
+    );
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..NUM_ROUNDS {
+        f();
+    }
+    start.elapsed() / NUM_ROUNDS
+}
+
+fn report(name: &str, elapsed: Duration, baseline: Duration) {
+    println!(
+        "{:<28} {:>10.1?} {:>6.1}x",
+        name,
+        elapsed,
+        baseline.as_secs_f64() / elapsed.as_secs_f64()
+    );
+}
@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use ava::{
+    bullet::{Bullet, BulletPlugin},
+    cell::{Cell, CellPlugin},
+    cli::CliArgs,
+    food::{Food, FoodPlugin},
+    physics::PhysicsPlugin,
+    recorder::StatsRecorderPlugin,
+    rng::RngPlugin,
+    settings::SettingsPlugin,
+    snapshot::SnapshotPlugin,
+    stats::{SimStats, StatsPlugin},
+    trackers::{SimClock, TrackersPlugin},
+    *,
+};
+use bevy::{
+    hierarchy::HierarchyPlugin, log::LogPlugin, prelude::*, time::common_conditions::on_timer,
+    transform::TransformPlugin,
+};
+
+/// Runs the simulation without a window, renderer or gui
+fn main() {
+    let args = CliArgs::parse();
+
+    App::new()
+        .add_plugins(MinimalPlugins)
+        .add_plugins(LogPlugin::default())
+        .add_plugins(TransformPlugin)
+        .add_plugins(HierarchyPlugin)
+        .insert_resource(args.config.clone())
+        .add_plugins(SettingsPlugin)
+        .add_plugins(TrackersPlugin)
+        .add_plugins(RngPlugin {
+            seed: args.config.seed,
+        })
+        .add_plugins(PhysicsPlugin)
+        .add_plugins(BulletPlugin)
+        .add_plugins(FoodPlugin)
+        .add_plugins(CellPlugin)
+        .add_plugins(StatsPlugin)
+        .add_plugins(StatsRecorderPlugin)
+        .add_plugins(SnapshotPlugin {
+            resume_from: args.resume_from,
+        })
+        .add_systems(
+            Update,
+            print_stats.run_if(on_timer(Duration::from_secs_f32(
+                HEADLESS_STATS_INTERVAL_SECS,
+            ))),
+        )
+        .run();
+}
+
+fn print_stats(
+    clock: Res<SimClock>,
+    stats: Res<SimStats>,
+    cells_query: Query<With<Cell>>,
+    food_query: Query<With<Food>>,
+    bullet_query: Query<With<Bullet>>,
+) {
+    println!(
+        "[{:.1} m] cells: {}, food: {}, bullets: {}, max energy: {:.1}, max age: {:.1}",
+        clock.elapsed() / 60.0,
+        cells_query.iter().len(),
+        food_query.iter().len(),
+        bullet_query.iter().len(),
+        stats.max_score,
+        stats.max_age,
+    );
+}
@@ -0,0 +1,238 @@
+use bevy::{math::vec2, prelude::*};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bullet::Bullet,
+    cell::Cell,
+    food::Food,
+    sim_config::SimConfig,
+    spatial::{Entry, SpatialGrid},
+    trackers::SimSet,
+    *,
+};
+
+/// Keeps the population within the `world_width` x `world_height` arena centered on the origin
+pub struct BoundaryPlugin;
+
+/// What happens at the edges of the arena
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum BoundaryMode {
+    /// Open space, cells can drift away forever
+    None,
+    /// Solid colliders along the edges
+    Walls,
+    /// Leaving through an edge comes back in through the opposite one,
+    /// sensors see across the edges too
+    Wrap,
+    /// Cells near or past an edge are pushed back in
+    Soft,
+}
+
+#[derive(Component)]
+pub struct Wall;
+
+/// Arena of the current config, cheap to build wherever it's needed
+#[derive(Clone, Copy)]
+pub struct Boundary {
+    pub mode: BoundaryMode,
+    pub half_size: Vec2,
+}
+
+impl Plugin for BoundaryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_walls).add_systems(
+            FixedUpdate,
+            (wrap_around, push_back_cells).in_set(SimSet::PostPhysics),
+        );
+    }
+}
+
+fn spawn_walls(mut commands: Commands, config: Res<SimConfig>) {
+    let boundary = Boundary::new(&config);
+    if boundary.mode != BoundaryMode::Walls {
+        return;
+    }
+
+    let Vec2 { x: hw, y: hh } = boundary.half_size;
+    let t = BOUNDARY_WALL_THICKNESS / 2.0;
+    let walls = [
+        (vec2(0.0, hh + t), vec2(hw + 2.0 * t, t)),
+        (vec2(0.0, -hh - t), vec2(hw + 2.0 * t, t)),
+        (vec2(hw + t, 0.0), vec2(t, hh)),
+        (vec2(-hw - t, 0.0), vec2(t, hh)),
+    ];
+    for (pos, half_extents) in walls {
+        commands.spawn((
+            Wall,
+            TransformBundle::from_transform(Transform::from_translation(pos.extend(0.0))),
+            RigidBody::Fixed,
+            Collider::cuboid(half_extents.x, half_extents.y),
+            CollisionGroups {
+                memberships: Group::from_bits_truncate(GRP_WALL),
+                filters: Group::from_bits_truncate(MASK_WALL),
+            },
+        ));
+    }
+}
+
+fn wrap_around(
+    config: Res<SimConfig>,
+    mut query: Query<&mut Transform, Or<(With<Cell>, With<Food>, With<Bullet>)>>,
+) {
+    let boundary = Boundary::new(&config);
+    if boundary.mode != BoundaryMode::Wrap {
+        return;
+    }
+
+    for mut transform in query.iter_mut() {
+        let pos = transform.translation.truncate();
+        let wrapped = boundary.wrap(pos);
+        // Only written when it moves, so rapier doesn't resync every body
+        if wrapped != pos {
+            transform.translation = wrapped.extend(transform.translation.z);
+        }
+    }
+}
+
+fn push_back_cells(
+    config: Res<SimConfig>,
+    mut query: Query<(&Transform, &mut Velocity), With<Cell>>,
+) {
+    let boundary = Boundary::new(&config);
+    if boundary.mode != BoundaryMode::Soft {
+        return;
+    }
+
+    let inner = boundary.half_size - BOUNDARY_SOFT_MARGIN;
+    for (transform, mut velocity) in query.iter_mut() {
+        let pos = transform.translation.truncate();
+        let depth = (pos.abs() - inner).max(Vec2::ZERO);
+        if depth == Vec2::ZERO {
+            continue;
+        }
+        // Grows the deeper a cell gets, past the edge it can't outrun the push
+        let push = -pos.signum() * depth / BOUNDARY_SOFT_MARGIN * BOUNDARY_SOFT_PUSH;
+        velocity.linvel += push * SIM_TICK_SECS;
+    }
+}
+
+impl Boundary {
+    pub fn new(config: &SimConfig) -> Self {
+        Self {
+            mode: config.boundary_mode,
+            half_size: vec2(config.world_width as f32, config.world_height as f32) / 2.0,
+        }
+    }
+
+    /// Same position brought back within the arena, only moves it when wrapping
+    pub fn wrap(&self, pos: Vec2) -> Vec2 {
+        if self.mode != BoundaryMode::Wrap {
+            return pos;
+        }
+
+        let size = self.half_size * 2.0;
+        (pos + self.half_size).rem_euclid(size) - self.half_size
+    }
+
+    /// Displacement from `from` to `to`, the shortest one across the edges when wrapping
+    pub fn offset(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let offset = to - from;
+        if self.mode != BoundaryMode::Wrap {
+            return offset;
+        }
+
+        let size = self.half_size * 2.0;
+        offset - (offset / size).round() * size
+    }
+
+    /// Closest entry of `grid`, looking across the edges when wrapping.
+    /// Returns it along with its position as seen from `pos`, which may lie past an edge
+    pub fn nearest<'a, T>(
+        &self,
+        grid: &'a SpatialGrid<T>,
+        pos: Vec2,
+        max_dist: f32,
+        filter: impl Fn(&Entry<T>) -> bool,
+    ) -> Option<(&'a Entry<T>, Vec2)> {
+        let mut best = grid.nearest(pos, max_dist, &filter).map(|e| (e, e.pos));
+        if self.mode != BoundaryMode::Wrap {
+            return best;
+        }
+
+        // Only images of the arena closer than the best so far can hold anything closer
+        let size = self.half_size * 2.0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+                let shift = vec2(dx as f32, dy as f32) * size;
+                let reach = best.map_or(max_dist, |(_, p)| p.distance(pos));
+                if self.distance_to_image(pos, dx, dy) > reach {
+                    continue;
+                }
+                if let Some(e) = grid.nearest(pos - shift, reach, &filter) {
+                    if e.pos.distance(pos - shift) < reach {
+                        best = Some((e, e.pos + shift));
+                    }
+                }
+            }
+        }
+
+        best
+    }
+
+    /// How far a ray from `pos`, which lies within the arena, goes along `direction` before it leaves
+    pub fn edge_distance(&self, pos: Vec2, direction: Vec2) -> f32 {
+        let to_edge = |p: f32, d: f32, half: f32| match d {
+            _ if d > 0.0 => (half - p) / d,
+            _ if d < 0.0 => (-half - p) / d,
+            _ => f32::INFINITY,
+        };
+        to_edge(pos.x, direction.x, self.half_size.x)
+            .min(to_edge(pos.y, direction.y, self.half_size.y))
+            .max(0.0)
+    }
+
+    /// Where a ray that reached the edge at `exit` comes back in, on the opposite edge
+    pub fn reenter(&self, exit: Vec2, direction: Vec2) -> Vec2 {
+        let cross = |p: f32, d: f32, half: f32| {
+            if d != 0.0 && half - p * d.signum() <= BOUNDARY_EDGE_TOLERANCE {
+                p - d.signum() * half * 2.0
+            } else {
+                p
+            }
+        };
+        vec2(
+            cross(exit.x, direction.x, self.half_size.x),
+            cross(exit.y, direction.y, self.half_size.y),
+        )
+    }
+
+    /// How far `pos` is from the closest wall, infinite when nothing stops the cells
+    pub fn wall_distance(&self, pos: Vec2) -> f32 {
+        match self.mode {
+            BoundaryMode::None | BoundaryMode::Wrap => f32::INFINITY,
+            BoundaryMode::Walls | BoundaryMode::Soft => {
+                let gap = self.half_size - pos.abs();
+                gap.x.min(gap.y).max(0.0)
+            }
+        }
+    }
+
+    /// Distance from `pos` to the copy of the arena shifted by `dx`, `dy` arena sizes
+    pub fn distance_to_image(&self, pos: Vec2, dx: i32, dy: i32) -> f32 {
+        let gap = |d: i32, p: f32, half: f32| match d {
+            1 => half - p,
+            -1 => half + p,
+            _ => 0.0,
+        };
+        vec2(
+            gap(dx, pos.x, self.half_size.x),
+            gap(dy, pos.y, self.half_size.y),
+        )
+        .max(Vec2::ZERO)
+        .length()
+    }
+}
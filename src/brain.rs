@@ -0,0 +1,250 @@
+use std::path::Path;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    neat::{FlatGenome, Genome, Innovations},
+    nn::{Activation, Crossover, FlatNet, Net, NetError},
+    sim_config::SimConfig,
+};
+
+/// Which kind of brain new cells are born with
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum BrainKind {
+    /// Fixed layers of `SimConfig::hidden_layers`, only the weights evolve
+    Dense,
+    /// NEAT genome that grows nodes and connections as it evolves
+    Neat,
+}
+
+/// What the simulation needs from a brain, whatever its topology
+pub trait BrainModel {
+    fn num_inputs(&self) -> usize;
+
+    /// Value of every node of `graph`, the output nodes come last
+    fn activate(&self, inputs: &[f64]) -> Vec<f64>;
+
+    fn mutate(
+        &mut self,
+        params: &MutationParams,
+        innovations: &mut Innovations,
+        rng: &mut impl Rng,
+    );
+
+    /// Child of `self`, the fitter parent, and `other`, fails when the two can't be lined up
+    fn crossover(
+        &self,
+        other: &Self,
+        kind: Crossover,
+        rng: &mut impl Rng,
+    ) -> Result<Self, NetError>
+    where
+        Self: Sized;
+
+    /// Nodes and connections, for drawing
+    fn graph(&self) -> BrainGraph;
+
+    /// Mean magnitude of all the weights and biases
+    fn mean_abs_weight(&self) -> f64;
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum BrainNet {
+    Dense(Net),
+    Neat(Genome),
+}
+
+/// Brain compiled for fast inference, see [`BrainNet::to_flat`]
+#[derive(Clone)]
+pub enum FlatBrain {
+    Dense(FlatNet),
+    Neat(FlatGenome),
+}
+
+pub struct MutationParams {
+    pub rate: f32,
+    pub variation: f32,
+    /// Chance of a dense hidden layer switching to another activation
+    pub activation_rate: f32,
+    /// Chance of a NEAT genome splitting a connection with a new node
+    pub add_node_rate: f32,
+    /// Chance of a NEAT genome connecting two unconnected nodes
+    pub add_connection_rate: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum NodeKind {
+    Input,
+    Hidden,
+    Output,
+}
+
+#[derive(Default, Clone)]
+pub struct BrainGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Clone)]
+pub struct GraphNode {
+    pub kind: NodeKind,
+    /// Inputs are at depth 0 and every node is deeper than the nodes feeding it
+    pub depth: usize,
+    pub activation: Activation,
+}
+
+/// Connection between two indices of `BrainGraph::nodes`
+#[derive(Clone)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub weight: f64,
+}
+
+impl BrainNet {
+    /// Random brain of the configured kind
+    pub fn new(config: &SimConfig, innovations: &mut Innovations, rng: &mut impl Rng) -> Self {
+        match config.brain_kind {
+            BrainKind::Dense => BrainNet::Dense(Net::new(
+                config.net_arch(),
+                &config.layer_activations(),
+                rng,
+            )),
+            BrainKind::Neat => BrainNet::Neat(Genome::new(
+                config.num_inputs(),
+                config.num_outputs(),
+                innovations,
+                rng,
+            )),
+        }
+    }
+
+    pub fn kind(&self) -> BrainKind {
+        match self {
+            BrainNet::Dense(_) => BrainKind::Dense,
+            BrainNet::Neat(_) => BrainKind::Neat,
+        }
+    }
+
+    /// NEAT genomes only have fixed input and output layers
+    pub fn layer_sizes(&self) -> Vec<usize> {
+        match self {
+            BrainNet::Dense(net) => net.layer_sizes(),
+            BrainNet::Neat(genome) => vec![genome.num_inputs(), genome.num_outputs()],
+        }
+    }
+
+    /// Whether the brain fits a sim whose dense brains have `net_arch` layers
+    pub fn fits(&self, net_arch: &[usize]) -> bool {
+        match self {
+            BrainNet::Dense(net) => net.layer_sizes() == net_arch,
+            BrainNet::Neat(genome) => {
+                Some(&genome.num_inputs()) == net_arch.first()
+                    && Some(&genome.num_outputs()) == net_arch.last()
+            }
+        }
+    }
+
+    /// Only needs rebuilding when the brain changes
+    pub fn to_flat(&self) -> FlatBrain {
+        match self {
+            BrainNet::Dense(net) => FlatBrain::Dense(net.to_flat()),
+            BrainNet::Neat(genome) => FlatBrain::Neat(genome.to_flat()),
+        }
+    }
+
+    /// Checks the invariants that a brain read from a file might break
+    pub fn validate(&self) -> Result<(), NetError> {
+        match self {
+            BrainNet::Dense(net) => net.validate(),
+            BrainNet::Neat(genome) => genome.validate(),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), NetError> {
+        match self {
+            BrainNet::Dense(net) => net.save(path),
+            BrainNet::Neat(genome) => genome.save(path),
+        }
+    }
+}
+
+impl FlatBrain {
+    /// Values written by `predict_into`, one per node including the inputs
+    pub fn num_values(&self) -> usize {
+        match self {
+            FlatBrain::Dense(flat) => flat.num_values(),
+            FlatBrain::Neat(flat) => flat.num_values(),
+        }
+    }
+
+    /// Same values as `BrainModel::activate` within f32 precision, without allocating
+    pub fn predict_into(&self, inputs: &[f64], values: &mut [f32]) {
+        match self {
+            FlatBrain::Dense(flat) => flat.predict_into(inputs, values),
+            FlatBrain::Neat(flat) => flat.predict_into(inputs, values),
+        }
+    }
+}
+
+impl BrainModel for BrainNet {
+    fn num_inputs(&self) -> usize {
+        match self {
+            BrainNet::Dense(net) => net.num_inputs(),
+            BrainNet::Neat(genome) => genome.num_inputs(),
+        }
+    }
+
+    fn activate(&self, inputs: &[f64]) -> Vec<f64> {
+        match self {
+            BrainNet::Dense(net) => net.activate(inputs),
+            BrainNet::Neat(genome) => genome.activate(inputs),
+        }
+    }
+
+    fn mutate(
+        &mut self,
+        params: &MutationParams,
+        innovations: &mut Innovations,
+        rng: &mut impl Rng,
+    ) {
+        match self {
+            BrainNet::Dense(net) => BrainModel::mutate(net, params, innovations, rng),
+            BrainNet::Neat(genome) => genome.mutate(params, innovations, rng),
+        }
+    }
+
+    fn crossover(
+        &self,
+        other: &Self,
+        kind: Crossover,
+        rng: &mut impl Rng,
+    ) -> Result<Self, NetError> {
+        match (self, other) {
+            (BrainNet::Dense(a), BrainNet::Dense(b)) => {
+                Ok(BrainNet::Dense(a.crossover(b, kind, rng)?))
+            }
+            (BrainNet::Neat(a), BrainNet::Neat(b)) => {
+                Ok(BrainNet::Neat(BrainModel::crossover(a, b, kind, rng)?))
+            }
+            _ => Err(NetError::BadShape(
+                "can't cross a dense net with a NEAT genome".to_string(),
+            )),
+        }
+    }
+
+    fn graph(&self) -> BrainGraph {
+        match self {
+            BrainNet::Dense(net) => net.graph(),
+            BrainNet::Neat(genome) => genome.graph(),
+        }
+    }
+
+    fn mean_abs_weight(&self) -> f64 {
+        match self {
+            BrainNet::Dense(net) => net.mean_abs_weight(),
+            BrainNet::Neat(genome) => genome.mean_abs_weight(),
+        }
+    }
+}
@@ -1,19 +1,40 @@
-use std::time::Instant;
-
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
-use crate::{cell::energy::EnergyMap, settings::DynamicSettings, trackers::BirthTs, *};
+use crate::{
+    cell::{
+        energy::{Energy, EnergyLedger, EnergyReason},
+        user::UserControlledCell,
+    },
+    events::BulletExpired,
+    settings::DynamicSettings,
+    sim_config::SimConfig,
+    spatial::SpatialGrid,
+    trackers::{BirthTs, SimClock, SimSet},
+    *,
+};
 
 pub struct BulletPlugin;
 
+/// Id of the cell that shot it
 #[derive(Component)]
 pub struct Bullet(pub u32);
+/// Entity of the cell that shot it, its energy is settled through it
+#[derive(Component)]
+pub struct BulletOwner(pub Entity);
+#[derive(Resource)]
+pub struct BulletGrid(pub SpatialGrid<SensedBullet>);
+
+pub struct SensedBullet {
+    pub owner: u32,
+    pub velocity: Vec2,
+}
 
 #[derive(Bundle)]
 pub struct BulletBundle {
     sprite_bundle: SpriteBundle,
     bullet: Bullet,
+    owner: BulletOwner,
     locked_axis: LockedAxes,
     birth_ts: BirthTs,
     rigid_body: RigidBody,
@@ -25,8 +46,11 @@ pub struct BulletBundle {
 
 impl Plugin for BulletPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup)
-            .add_systems(Update, bullet_cleanup);
+        app.insert_resource(BulletGrid(SpatialGrid::new(SPATIAL_GRID_CELL_SIZE)))
+            .add_event::<BulletExpired>()
+            .add_systems(Startup, setup)
+            .add_systems(FixedUpdate, bullet_cleanup.in_set(SimSet::Logic))
+            .add_systems(FixedUpdate, update_bullet_grid.in_set(SimSet::Index));
     }
 }
 
@@ -34,37 +58,78 @@ fn setup() {}
 
 fn bullet_cleanup(
     mut commands: Commands,
-    mut energy_map: ResMut<EnergyMap>,
+    clock: Res<SimClock>,
+    config: Res<SimConfig>,
     settings: Res<DynamicSettings>,
-    bullet_query: Query<(Entity, &BirthTs, &Bullet), With<Bullet>>,
+    mut writer: EventWriter<BulletExpired>,
+    bullet_query: Query<(Entity, &BirthTs, &Bullet, &BulletOwner, &Transform)>,
+    mut energy_query: Query<(&mut Energy, &mut EnergyLedger), Without<UserControlledCell>>,
 ) {
-    for (entity, birth_ts, b) in bullet_query.iter() {
-        if birth_ts.0.elapsed_within(BULLET_LIFESPAN) {
+    for (entity, birth_ts, b, owner, transform) in bullet_query.iter() {
+        if birth_ts.0.elapsed_within(&clock, config.bullet_lifespan) {
             continue;
         }
         commands.entity(entity).despawn();
 
-        match energy_map.0.get_mut(&b.0) {
-            Some((v, i)) => {
-                *v -= settings.bullet_miss_penalty;
-                *i = Instant::now();
-            }
-            None => {}
+        // The owner may have died since, the user cell never pays
+        let mut energy_delta = 0.0;
+        if let Ok((mut energy, mut ledger)) = energy_query.get_mut(owner.0) {
+            energy_delta = -settings.bullet_miss_penalty;
+            energy.0 += energy_delta;
+            ledger.record(EnergyReason::MissPenalty, energy_delta);
         }
+        writer.send(BulletExpired {
+            owner: b.0,
+            pos: transform.translation.truncate(),
+            energy_delta,
+        });
+    }
+}
+
+fn update_bullet_grid(
+    mut bullet_grid: ResMut<BulletGrid>,
+    mut removed: RemovedComponents<Bullet>,
+    moved_query: Query<
+        (Entity, &Bullet, &Transform, &Velocity),
+        Or<(Changed<Transform>, Changed<Velocity>)>,
+    >,
+    bullet_query: Query<(), With<Bullet>>,
+) {
+    for entity in removed.iter() {
+        bullet_grid.0.remove(entity);
+    }
+    for (entity, b, t, velocity) in moved_query.iter() {
+        let bullet = SensedBullet {
+            owner: b.0,
+            velocity: velocity.linvel,
+        };
+        bullet_grid
+            .0
+            .insert(entity, t.translation.truncate(), bullet);
     }
+    bullet_grid
+        .0
+        .prune(bullet_query.iter().len(), |e| bullet_query.contains(e));
 }
 
 impl BulletBundle {
-    pub fn new(x: f32, y: f32, cell_id: u32, direction: Vec2, asset_server: &AssetServer) -> Self {
+    pub fn new(
+        x: f32,
+        y: f32,
+        cell_id: u32,
+        owner: Entity,
+        direction: Vec2,
+        clock: &SimClock,
+    ) -> Self {
         Self {
             sprite_bundle: SpriteBundle {
                 transform: Transform::from_xyz(x, y, 0.0),
-                texture: asset_server.load(BULLET_SPRITE),
                 ..default()
             },
             bullet: Bullet(cell_id),
+            owner: BulletOwner(owner),
             locked_axis: LockedAxes::ROTATION_LOCKED,
-            birth_ts: BirthTs::default(),
+            birth_ts: BirthTs(clock.now()),
             rigid_body: RigidBody::Dynamic,
             collider: Collider::ball(4.0),
             velocity: Velocity::linear(direction),
@@ -3,8 +3,8 @@ use bevy_pancam::{PanCam, PanCamPlugin};
 
 use crate::{
     cell::{focus::FocusedCellStats, user::UserControlledCell, Cell},
-    gui::SimStats,
     settings::SimSettings,
+    stats::SimStats,
 };
 
 pub struct FollowCameraPlugin;
@@ -4,14 +4,21 @@ use bevy_rapier2d::prelude::*;
 use rand::Rng;
 
 use crate::trackers::*;
-use crate::{nn::Net, *};
+use crate::{brain::BrainNet, sim_config::SimConfig, *};
 
-use super::{Brain, Cell};
+use super::{
+    energy::{Energy, EnergyLedger},
+    genealogy::Ancestry,
+    traits::BodyTraits,
+    Brain, BrainBuffers, Cell, Lineage, Memory,
+};
 
 #[derive(Bundle)]
 pub struct CellBundle {
     sprite_bundle: SpriteBundle,
     cell: Cell,
+    lineage: Lineage,
+    ancestry: Ancestry,
     birth_place: BirthPlace,
     birth_ts: BirthTs,
     last_bullet_fired: LastBulletFired,
@@ -20,7 +27,13 @@ pub struct CellBundle {
     rigid_body: RigidBody,
     collider: Collider,
     damping: Damping,
+    velocity: Velocity,
     brain: Brain,
+    memory: Memory,
+    traits: BodyTraits,
+    energy: Energy,
+    ledger: EnergyLedger,
+    brain_buffers: BrainBuffers,
     num_cells_spawned: NumCellsSpawned,
     fitness_score: FitnessScores,
     external_force: ExternalForce,
@@ -28,37 +41,47 @@ pub struct CellBundle {
 }
 
 impl CellBundle {
+    /// The sprite texture is assigned by the `VisualsPlugin` when rendering is enabled
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         x: f32,
         y: f32,
         cell_id: u32,
-        net: Net,
-        sprite_path: &str,
-        asset_server: &AssetServer,
+        net: BrainNet,
+        traits: BodyTraits,
+        config: &SimConfig,
+        clock: &SimClock,
+        rng: &mut impl Rng,
     ) -> Self {
-        let mut rng = rand::thread_rng();
         let rot = rng.gen_range(0.0..6.0);
         Self {
             sprite_bundle: SpriteBundle {
                 transform: Transform::from_xyz(x, y, 1.0)
                     .with_rotation(Quat::from_rotation_z(rot))
-                    .with_scale(Vec3::splat(1.5)),
-                texture: asset_server.load(sprite_path),
+                    .with_scale(Vec3::splat(traits.sprite_scale(config))),
                 ..default()
             },
             cell: Cell(cell_id),
+            lineage: Lineage(cell_id),
+            ancestry: Ancestry::founder(),
             birth_place: BirthPlace(vec2(x, y)),
-            birth_ts: BirthTs::default(),
-            last_bullet_fired: LastBulletFired::default(),
+            birth_ts: BirthTs(clock.now()),
+            last_bullet_fired: LastBulletFired(clock.now()),
             periodic_update_interval: PeriodicUpdateInterval(rng.gen_range(0.0..=1.0)),
-            last_updated: LastUpdated::default(),
+            last_updated: LastUpdated(clock.now()),
             rigid_body: RigidBody::Dynamic,
-            collider: Collider::ball(7.0),
+            collider: Collider::ball(traits.size),
             damping: Damping {
                 angular_damping: 2.0,
                 linear_damping: 2.0,
             },
+            velocity: Velocity::zero(),
             brain: Brain(net),
+            memory: Memory::default(),
+            traits,
+            energy: Energy(config.base_energy),
+            ledger: EnergyLedger::default(),
+            brain_buffers: BrainBuffers::default(),
             num_cells_spawned: NumCellsSpawned(0),
             fitness_score: FitnessScores::new(),
             external_force: ExternalForce {
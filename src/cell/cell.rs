@@ -1,30 +1,39 @@
-use std::{
-    f32::consts::PI,
-    time::{Duration, Instant},
-};
+use std::f32::consts::PI;
 
-use bevy::{math::vec2, prelude::*, time::common_conditions::on_timer};
+use bevy::{math::vec2, prelude::*, utils::HashMap};
 use bevy_rapier2d::prelude::*;
-use rand::Rng;
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    bullet::BulletBundle,
-    food::FoodTree,
-    gui::SimStats,
-    nn::Net,
-    settings::SimSettings,
+    boundary::Boundary,
+    brain::{BrainModel, BrainNet, FlatBrain},
+    bullet::{BulletBundle, BulletGrid},
+    events::{BulletFired, CellBorn},
+    fitness::{FitnessPlugin, FitnessRegistry},
+    food::FoodGrid,
+    neat::{NeatPlugin, NeatState, Species},
+    rng::SimRng,
+    settings::DynamicSettings,
+    sim_config::SimConfig,
+    spatial::SpatialGrid,
+    stats::{Demographics, SimStats},
     trackers::{
-        BirthPlace, BirthTs, FitnessScores, LastBulletFired, LastUpdated, NumCellsSpawned,
-        OneSecondTimer, PeriodicUpdateInterval,
+        on_sim_timer, FitnessScores, LastBulletFired, LastUpdated, NumCellsSpawned, OneSecondTimer,
+        PeriodicUpdateInterval, SimClock, SimSet,
     },
     *,
 };
 
 use super::{
     bundle::CellBundle,
-    energy::{CellEnergyPlugin, EnergyMap},
+    energy::{CellEnergyPlugin, Energy, EnergyLedger, EnergyReason},
     focus::{CellFocusPlugin, FocusedCellNet, FocusedCellStats},
-    user::{UserCellPlugin, UserControlledCell},
+    genealogy::{Ancestry, GenealogyPlugin},
+    kill_rules::KillRulesPlugin,
+    sensors::{sense_energy, sense_nearest_bullet, sense_nearest_enemy, sense_wall, Vision},
+    traits::BodyTraits,
+    user::UserControlledCell,
 };
 
 pub struct CellPlugin;
@@ -34,7 +43,46 @@ pub struct Cell(pub u32);
 #[derive(Resource)]
 pub struct CellId(pub u32);
 #[derive(Component)]
-pub struct Brain(pub Net);
+pub struct Brain(pub BrainNet);
+/// Id of the founding ancestor, shared by all of its descendants
+#[derive(Component)]
+pub struct Lineage(pub u32);
+/// Memory outputs of the last update, fed back to the brain on the next one.
+/// Cells are born with it empty and it is zeroed on their first update
+#[derive(Component, Default)]
+pub struct Memory(pub Vec<f64>);
+/// Scratch space reused across brain updates, so that thinking doesn't allocate
+#[derive(Component, Default)]
+pub struct BrainBuffers {
+    /// Compiled copy of the brain, rebuilt whenever the brain changes
+    flat: Option<FlatBrain>,
+    inputs: Vec<f64>,
+    values: Vec<f32>,
+    /// Set once the brain ran, until its actions are performed
+    is_fresh: bool,
+}
+/// Set on a cell once a bullet takes its last energy, it's then despawned by the `KillRulesPlugin`
+#[derive(Component)]
+pub struct KilledInCombat;
+#[derive(Resource)]
+pub struct CellGrid(pub SpatialGrid<SensedCell>);
+
+/// What other cells can tell about a cell from a distance
+pub struct SensedCell {
+    pub entity: Entity,
+    pub id: u32,
+    pub lineage: u32,
+    pub velocity: Vec2,
+}
+
+/// How a replicating cell picks the other parent when crossover is enabled
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum MateSelection {
+    /// The closest living cell
+    Nearest,
+    /// Any living cell, with odds proportional to its energy
+    Roulette,
+}
 
 pub struct CellAction {
     pub thrust: bool,
@@ -47,256 +95,272 @@ impl Plugin for CellPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(CellEnergyPlugin)
             .add_plugins(CellFocusPlugin)
-            .add_plugins(UserCellPlugin)
+            .add_plugins(GenealogyPlugin)
+            .add_plugins(NeatPlugin)
+            .add_plugins(FitnessPlugin)
+            .add_plugins(KillRulesPlugin)
+            .add_event::<CellBorn>()
+            .add_event::<BulletFired>()
             .insert_resource(CellId(0))
+            .insert_resource(CellGrid(SpatialGrid::new(SPATIAL_GRID_CELL_SIZE)))
             .add_systems(Startup, setup)
-            .add_systems(Update, update_cells_system)
-            .add_systems(Update, update_cell_sprite)
             .add_systems(
-                Update,
-                kill_bad_cells.run_if(on_timer(Duration::from_secs_f32(0.5))),
+                FixedUpdate,
+                (think_cells_system, act_cells_system)
+                    .chain()
+                    .in_set(SimSet::Logic),
             )
             .add_systems(
-                Update,
-                cell_replication_system.run_if(on_timer(Duration::from_secs_f32(0.5))),
+                FixedUpdate,
+                cell_replication_system
+                    .run_if(on_sim_timer(0.5))
+                    .in_set(SimSet::Logic),
             )
             .add_systems(
-                Update,
-                spawn_cells.run_if(on_timer(Duration::from_secs_f32(5.0))),
-            );
+                FixedUpdate,
+                spawn_cells.run_if(on_sim_timer(5.0)).in_set(SimSet::Logic),
+            )
+            .add_systems(FixedUpdate, update_cell_grid.in_set(SimSet::Index));
     }
 }
 
 fn setup(
     commands: Commands,
+    clock: Res<SimClock>,
+    config: Res<SimConfig>,
+    rng: ResMut<SimRng>,
     cell_id: ResMut<CellId>,
-    asset_server: Res<AssetServer>,
+    demographics: ResMut<Demographics>,
+    neat: NeatState,
+    born_writer: EventWriter<CellBorn>,
     cell_query: Query<(With<Cell>, Without<UserControlledCell>)>,
 ) {
-    spawn_cells(commands, cell_id, asset_server, cell_query);
+    spawn_cells(
+        commands,
+        clock,
+        config,
+        rng,
+        cell_id,
+        demographics,
+        neat,
+        born_writer,
+        cell_query,
+    );
 }
 
-fn kill_bad_cells(
-    mut commands: Commands,
-    mut energy_map: ResMut<EnergyMap>,
-    cell_query: Query<
+/// Senses and runs the brain of every cell due for an update, in parallel.
+/// The actions are performed afterwards by `act_cells_system`
+fn think_cells_system(
+    clock: Res<SimClock>,
+    config: Res<SimConfig>,
+    one_second_timer: Res<OneSecondTimer>,
+    food_grid: Res<FoodGrid>,
+    cell_grid: Res<CellGrid>,
+    bullet_grid: Res<BulletGrid>,
+    settings: Res<DynamicSettings>,
+    fitness_registry: Res<FitnessRegistry>,
+    vision: Vision,
+    mut cell_query: Query<
         (
+            Entity,
             &Cell,
+            &Lineage,
             &Transform,
-            &BirthPlace,
-            &BirthTs,
-            &LastBulletFired,
-            Entity,
+            &Velocity,
+            Ref<Brain>,
+            &mut BrainBuffers,
+            &mut Memory,
+            &mut LastUpdated,
+            &mut FitnessScores,
+            &PeriodicUpdateInterval,
+            (&BodyTraits, &Energy),
         ),
-        (With<Cell>, Without<UserControlledCell>),
+        Without<UserControlledCell>,
     >,
 ) {
-    for (c, transform, birth_place, birth_ts, last_bullet_fired, entity) in cell_query.iter() {
-        // Low energy cells
-        match energy_map.0.get(&c.0) {
-            Some((v, _)) => {
-                if *v <= 0.0 {
-                    energy_map.0.remove(&c.0);
-                    commands.entity(entity).despawn();
-                    continue;
-                }
+    let boundary = Boundary::new(&config);
+    let fitness = fitness_registry.get(&settings.fitness);
+    cell_query.par_iter_mut().for_each_mut(
+        |(
+            entity,
+            cell,
+            lineage,
+            transform,
+            velocity,
+            brain,
+            mut buffers,
+            mut memory,
+            mut last_updated,
+            mut fitness_scores,
+            periodic_update_interval,
+            (traits, energy),
+        )| {
+            if brain.is_changed() {
+                buffers.compile(&brain.0);
             }
-            None => {}
-        }
-
-        // Unmoving cells
-        if birth_ts.0.elapsed_past(10.0) && birth_ts.0.elapsed_within(20.0) {
-            if birth_place
+            if last_updated
                 .0
-                .distance_squared(transform.translation.truncate())
-                < 50.0
+                .elapsed_within(&clock, config.update_interval)
             {
-                energy_map.0.remove(&c.0);
-                commands.entity(entity).despawn();
-                continue;
+                return;
             }
-        }
-
-        // Revolving
-        if birth_ts.0.elapsed_past(15.0) && birth_ts.0.elapsed_within(18.0) {
-            if birth_place
+            if one_second_timer
                 .0
-                .distance_squared(transform.translation.truncate())
-                < 25000.0
+                .elapsed_within(&clock, periodic_update_interval.0)
             {
-                energy_map.0.remove(&c.0);
-                commands.entity(entity).despawn();
-                continue;
+                return;
             }
-        }
-
-        // Traveling in one direction only
-        if birth_ts.0.elapsed_past(20.0) && birth_ts.0.elapsed_within(30.0) {
-            let x_disp = (transform.translation.x - birth_place.0.x).abs();
-            let y_disp = (transform.translation.y - birth_place.0.y).abs();
 
-            if x_disp * 3.0 < y_disp || y_disp * 3.0 < x_disp {
-                energy_map.0.remove(&c.0);
-                commands.entity(entity).despawn();
-                continue;
-            }
-        }
-
-        // No bullets fired
-        if last_bullet_fired.0.elapsed_past(8.0) {
-            match energy_map.0.get_mut(&c.0) {
-                Some((v, i)) => {
-                    *v -= NO_BULLET_PENALTY;
-                    *i = Instant::now();
-                }
-                None => {}
+            last_updated.0.set_now(&clock);
+            let mut target_x = 0.0;
+            let mut target_y = 0.0;
+            // Get the closest food, possibly across an edge of a wrapped world
+            let pos = transform.translation.truncate();
+            if let Some((_, food_pos)) =
+                boundary.nearest(&food_grid.0, pos, f32::INFINITY, |_| true)
+            {
+                target_x = food_pos.x;
+                target_y = food_pos.y;
             }
-        }
-    }
-}
-
-fn update_cell_sprite(
-    settings: Res<SimSettings>,
-    asset_server: Res<AssetServer>,
-    stats: Res<SimStats>,
-    energy_map: Res<EnergyMap>,
-    mut cell_query: Query<(&Cell, &BirthTs, &Transform, &mut Handle<Image>), With<Cell>>,
-) {
-    if !settings.follow_best && !settings.follow_oldest {
-        return;
-    }
 
-    for (cell, birth_ts, transform, mut image_handle) in cell_query.iter_mut() {
-        if settings.follow_best
-            && transform
+            // NN Inputs
+            let nn_inp_dist = transform
                 .translation
                 .truncate()
-                .distance_squared(stats.best_cell_pos)
-                <= 200.0
-        {
-            match energy_map.0.get(&cell.0) {
-                Some((v, _)) => {
-                    if *v == stats.max_score {
-                        *image_handle = asset_server.load(FOCUSED_CELL_SPRITE);
-                    }
-                }
-                None => {}
+                .distance(vec2(target_x as f32, target_y as f32))
+                / traits.vision_radius;
+            let nn_inp_dist = if nn_inp_dist > 1.0 { 1.0 } else { nn_inp_dist };
+
+            let nn_inp_angle = angle_between(
+                transform.translation.x,
+                transform.translation.y,
+                target_x,
+                target_y,
+            );
+            let nn_inp_angle = nn_inp_angle / 360.0;
+            let nn_target_angle = if nn_inp_angle < 0.0 {
+                nn_inp_angle + 360.0
+            } else {
+                nn_inp_angle
+            };
+
+            let nn_cell_angle =
+                (transform.rotation.to_euler(EulerRot::XYZ).2 + PI / 2.0).to_degrees();
+            let nn_cell_angle = if nn_cell_angle < 0.0 {
+                nn_cell_angle + 360.0
+            } else {
+                nn_cell_angle
+            };
+            let nn_cell_angle = nn_cell_angle / 360.0;
+
+            let input = &mut buffers.inputs;
+            input.clear();
+            input.extend([
+                nn_inp_dist as f64,
+                nn_target_angle as f64,
+                nn_cell_angle as f64,
+            ]);
+            if config.is_cell_sensor_enabled {
+                input.extend(sense_nearest_enemy(
+                    &config,
+                    &cell_grid,
+                    pos,
+                    velocity.linvel,
+                    nn_cell_angle,
+                    traits.vision_radius,
+                    cell.0,
+                    lineage.0,
+                ));
             }
-        } else if settings.follow_oldest
-            && transform
-                .translation
-                .truncate()
-                .distance_squared(stats.oldest_cell_pos)
-                <= 200.0
-            && birth_ts.0.elapsed() as i32 == stats.max_age as i32
-        {
-            *image_handle = asset_server.load(FOCUSED_CELL_SPRITE);
-        } else {
-            *image_handle = asset_server.load(CELL_SPRITE);
-        }
-    }
+            if config.is_bullet_sensor_enabled {
+                input.extend(sense_nearest_bullet(
+                    &config,
+                    &bullet_grid,
+                    pos,
+                    velocity.linvel,
+                    nn_cell_angle,
+                    traits.vision_radius,
+                    cell.0,
+                ));
+            }
+            if config.is_energy_sensor_enabled {
+                input.push(sense_energy(&config, energy.0));
+            }
+            if config.is_vision_enabled {
+                let angle = transform.rotation.to_euler(EulerRot::XYZ).2 + PI / 2.0;
+                input.extend(vision.sense(entity, pos, angle, traits.vision_radius));
+            }
+            if config.is_wall_sensor_enabled {
+                input.push(sense_wall(&config, pos, traits.vision_radius));
+            }
+            if memory.0.len() != config.num_memory_neurons {
+                memory.0 = vec![0.0; config.num_memory_neurons];
+            }
+            input.extend(memory.0.iter());
+
+            // Update brain
+            buffers.run();
+            let output = buffers.outputs(config.num_outputs());
+            for (m, v) in memory.0.iter_mut().zip(&output[NUM_OUTPUT_NODES..]) {
+                *m = *v as f64;
+            }
+
+            let output = [
+                output[0] as f64,
+                output[1] as f64,
+                output[2] as f64,
+                output[3] as f64,
+            ];
+            let fitness = fitness.score(&buffers.inputs, &output);
+            fitness_scores.push(fitness);
+        },
+    );
 }
 
-fn update_cells_system(
+/// Performs the actions picked by the brains that ran in `think_cells_system`
+fn act_cells_system(
     mut commands: Commands,
-    one_second_timer: Res<OneSecondTimer>,
-    asset_server: Res<AssetServer>,
-    food_tree: Res<FoodTree>,
+    clock: Res<SimClock>,
+    config: Res<SimConfig>,
     focused_cell_stats: Res<FocusedCellStats>,
     mut focused_cell_net: ResMut<FocusedCellNet>,
+    mut fired_writer: EventWriter<BulletFired>,
     mut cell_query: Query<
         (
+            Entity,
             &Cell,
-            &mut Transform,
             &Brain,
+            &mut BrainBuffers,
+            &mut Transform,
             &mut ExternalForce,
-            &mut LastUpdated,
             &mut LastBulletFired,
-            &mut FitnessScores,
-            &PeriodicUpdateInterval,
+            &BodyTraits,
         ),
-        (With<Cell>, Without<UserControlledCell>),
+        Without<UserControlledCell>,
     >,
 ) {
     for (
+        entity,
         cell,
-        mut transform,
         brain,
+        mut buffers,
+        mut transform,
         mut external_force,
-        mut last_updated,
         mut last_bullet_fired,
-        mut fitness_scores,
-        periodic_update_interval,
+        traits,
     ) in cell_query.iter_mut()
     {
-        if last_updated.0.elapsed_within(UPDATE_INTERVAL) {
-            continue;
-        }
-        if one_second_timer
-            .0
-            .elapsed_within(periodic_update_interval.0)
-        {
+        if !buffers.is_fresh {
             continue;
         }
+        buffers.is_fresh = false;
 
-        last_updated.0.set_instant_now();
-        let mut target_x = 0.0;
-        let mut target_y = 0.0;
-        // Get the closest food
-        let key = (transform.translation.x, transform.translation.y);
-        if let Some(t) = &food_tree.0 {
-            match t.nearest(&[key.0 as f32, key.1 as f32]) {
-                Some(v) => {
-                    let [x, y] = v.item;
-                    target_x = *x;
-                    target_y = *y;
-                }
-                None => {}
-            }
-        }
-
-        // NN Inputs
-        let nn_inp_dist = transform
-            .translation
-            .truncate()
-            .distance(vec2(target_x as f32, target_y as f32))
-            / VISION_RADIUS;
-        let nn_inp_dist = if nn_inp_dist > 1.0 { 1.0 } else { nn_inp_dist };
-
-        let nn_inp_angle = angle_between(
-            transform.translation.x,
-            transform.translation.y,
-            target_x,
-            target_y,
-        );
-        let nn_inp_angle = nn_inp_angle / 360.0;
-        let nn_target_angle = if nn_inp_angle < 0.0 {
-            nn_inp_angle + 360.0
-        } else {
-            nn_inp_angle
-        };
-
-        let nn_cell_angle = (transform.rotation.to_euler(EulerRot::XYZ).2 + PI / 2.0).to_degrees();
-        let nn_cell_angle = if nn_cell_angle < 0.0 {
-            nn_cell_angle + 360.0
-        } else {
-            nn_cell_angle
-        };
-        let nn_cell_angle = nn_cell_angle / 360.0;
-        // let angle_diff = nn_cell_angle / 360.0;
-
-        // Update brain
-        let input = [
-            nn_inp_dist as f64,
-            nn_target_angle as f64,
-            nn_cell_angle as f64,
-        ];
-        let output = &brain.0.predict(&input.to_vec());
         if focused_cell_stats.id == cell.0 {
-            focused_cell_net.0 = output.clone();
+            focused_cell_net.graph = brain.0.graph();
+            focused_cell_net.values = buffers.values.iter().map(|v| *v as f64).collect();
         }
 
-        let output = &output[NET_ARCH.len() - 1];
+        let output = buffers.outputs(config.num_outputs());
         let mut spin_left = false;
         let mut spin_right = false;
         let thrust = output[2] >= 0.7;
@@ -308,42 +372,50 @@ fn update_cells_system(
             spin_right = true;
         }
 
-        let fitness = calc_fitness(input, [output[0], output[1], output[2], output[3]]);
-        fitness_scores.push(fitness);
-
         let action = CellAction {
             thrust,
             spin_left,
             spin_right,
             shoot,
         };
-        perform_cell_action(
+        let fired = perform_cell_action(
             action,
             cell.0,
+            entity,
             &mut last_bullet_fired,
             &mut external_force,
             &mut commands,
             &mut transform,
-            &asset_server,
+            traits,
+            &clock,
+            &config,
         );
+        if let Some(fired) = fired {
+            fired_writer.send(fired);
+        }
     }
 }
 
+/// Returns the bullet fired, if any, for the caller to send
+#[allow(clippy::too_many_arguments)]
 pub fn perform_cell_action(
     action: CellAction,
     cell_id: u32,
+    entity: Entity,
     last_bullet_fired: &mut LastBulletFired,
     external_force: &mut ExternalForce,
     commands: &mut Commands,
     transform: &mut Transform,
-    asset_server: &AssetServer,
-) {
+    traits: &BodyTraits,
+    clock: &SimClock,
+    config: &SimConfig,
+) -> Option<BulletFired> {
     let spin_strength = 0.5;
 
     // Apply Cell force
     let angle = (transform.rotation.to_euler(EulerRot::XYZ).2 + PI / 2.0) as f64;
     if action.thrust {
-        external_force.force = vec2(angle.cos() as f32, angle.sin() as f32) * CELL_SPEED;
+        external_force.force = vec2(angle.cos() as f32, angle.sin() as f32) * traits.speed;
     } else {
         external_force.force = Vec2::ZERO;
     }
@@ -355,10 +427,10 @@ pub fn perform_cell_action(
     }
 
     if !action.shoot {
-        return;
+        return None;
     }
-    if last_bullet_fired.0.elapsed_within(BULLET_FIRE_RATE) {
-        return;
+    if last_bullet_fired.0.elapsed_within(clock, traits.fire_rate) {
+        return None;
     }
 
     // Bullet spawn
@@ -366,72 +438,196 @@ pub fn perform_cell_action(
     let x = angle.cos();
     let y = angle.sin();
     let direction = vec2(x, y);
-    let x = transform.translation.x + (x * 5.0);
-    let y = transform.translation.y + (y * 5.0);
-    last_bullet_fired.0.set_instant_now();
-    commands.spawn(BulletBundle::new(
-        x,
-        y,
-        cell_id,
-        direction * BULLET_SPEED,
-        &asset_server,
-    ));
+    // Spawned clear of the cell's own collider
+    let offset = traits.size + 9.0;
+    let x = transform.translation.x + (x * offset);
+    let y = transform.translation.y + (y * offset);
+    let velocity = direction * config.bullet_speed;
+    last_bullet_fired.0.set_now(clock);
+    commands.spawn(BulletBundle::new(x, y, cell_id, entity, velocity, clock));
+
+    Some(BulletFired {
+        owner: cell_id,
+        pos: vec2(x, y),
+        velocity,
+    })
 }
 
 fn cell_replication_system(
     mut commands: Commands,
+    clock: Res<SimClock>,
+    config: Res<SimConfig>,
+    mut rng: ResMut<SimRng>,
     mut cell_id: ResMut<CellId>,
-    energy_map: Res<EnergyMap>,
     stats: Res<SimStats>,
-    asset_server: Res<AssetServer>,
-    mut cell_query: Query<(&Cell, &Brain, &mut NumCellsSpawned), With<Cell>>,
+    mut demographics: ResMut<Demographics>,
+    mut neat: NeatState,
+    mut born_writer: EventWriter<CellBorn>,
+    cell_grid: Res<CellGrid>,
+    mut cell_query: Query<
+        (
+            Entity,
+            &Cell,
+            (&Brain, &BodyTraits),
+            (&mut Energy, &mut EnergyLedger),
+            Option<&Species>,
+            &Lineage,
+            &Ancestry,
+            &Transform,
+            &mut NumCellsSpawned,
+        ),
+        Without<UserControlledCell>,
+    >,
+    mate_query: Query<
+        (Entity, (&Brain, &BodyTraits), Option<&Species>),
+        Without<UserControlledCell>,
+    >,
 ) {
+    let rng = &mut rng.0;
+    let (w, h) = (config.world_width as f32, config.world_height as f32);
     let mut num_cells = cell_query.iter().len();
-    for (c, brain, mut num_cells_spawned) in cell_query.iter_mut() {
-        let mut rng = rand::thread_rng();
-        if num_cells >= NUM_CELLS {
+
+    // Visited in id order so that the rng draws don't depend on the table order,
+    // which changes when a snapshot is loaded
+    let mut parents: Vec<_> = cell_query.iter().map(|(e, c, ..)| (c.0, e)).collect();
+    parents.sort_unstable();
+    // Energy at the start of the pass, the weights of the roulette and the fitness of mates
+    let energies: HashMap<Entity, f32> = cell_query
+        .iter()
+        .map(|(e, _, _, (energy, _), ..)| (e, energy.0))
+        .collect();
+
+    let roulette = match (config.is_crossover_enabled, config.mate_selection) {
+        (true, MateSelection::Roulette) => {
+            let (entities, weights): (Vec<_>, Vec<_>) = parents
+                .iter()
+                .map(|(_, e)| (*e, energies[e].max(0.0)))
+                .unzip();
+            WeightedIndex::new(weights).ok().map(|w| (entities, w))
+        }
+        _ => None,
+    };
+
+    for (_, entity) in parents {
+        if num_cells >= config.num_cells {
             continue;
         }
+        let Ok((
+            entity,
+            c,
+            (brain, traits),
+            (mut energy, mut ledger),
+            species,
+            lineage,
+            ancestry,
+            transform,
+            mut num_cells_spawned,
+        )) = cell_query.get_mut(entity)
+        else {
+            continue;
+        };
 
-        match energy_map.0.get(&c.0) {
-            Some((v, _)) => {
-                if rng.gen_range(0.0..1.0) >= (v / stats.max_score) {
-                    continue;
+        if rng.gen_range(0.0..1.0) >= (energy.0 / stats.max_score) {
+            continue;
+        }
+        // if rng.gen_range(0.0..100.0) >= (birth_ts.0.elapsed() / stats.max_age) * 20.0 {
+        //     continue;
+        // }
+        // if rng.gen_range(0.0..100.0) >= 20.0 {
+        //     continue;
+        // }
+
+        let x = rng.gen_range(-w / 2.0..w / 2.0);
+        let y = rng.gen_range(-h / 2.0..h / 2.0);
+        let mate = match (config.is_crossover_enabled, config.mate_selection) {
+            (false, _) => None,
+            (true, MateSelection::Nearest) => {
+                nearest_mate(&cell_grid, entity, transform.translation.truncate())
+            }
+            (true, MateSelection::Roulette) => roulette
+                .as_ref()
+                .map(|(entities, weights)| entities[weights.sample(rng)])
+                .filter(|e| *e != entity),
+        };
+        // Different species don't mix, the child is then a clone
+        let mate = mate
+            .and_then(|e| mate_query.get(e).ok())
+            .filter(|(_, _, mate_species)| *mate_species == species);
+        let mut child_net = match mate {
+            Some((mate_entity, (mate_brain, _), _)) => {
+                // The fitter parent goes first, NEAT children take their structure from it
+                let (fitter, other) = if energies.get(&mate_entity) > energies.get(&entity) {
+                    (&mate_brain.0, &brain.0)
+                } else {
+                    (&brain.0, &mate_brain.0)
+                };
+                match fitter.crossover(other, config.crossover, rng) {
+                    Ok(net) => net,
+                    Err(e) => {
+                        warn!("Crossover failed, cloning cell {}: {}", c.0, e);
+                        brain.0.clone()
+                    }
                 }
-                // if rng.gen_range(0.0..100.0) >= (birth_ts.0.elapsed() / stats.max_age) * 20.0 {
-                //     continue;
-                // }
-                // if rng.gen_range(0.0..100.0) >= 20.0 {
-                //     continue;
-                // }
-
-                let x = rng.gen_range(-(W as f32) / 2.0..W as f32 / 2.0);
-                let y = rng.gen_range(-(H as f32) / 2.0..H as f32 / 2.0);
-                let mut child_net = brain.0.clone();
-                child_net.mutate();
-
-                cell_id.0 += 1;
-                num_cells += 1;
-
-                num_cells_spawned.0 += 1;
-                commands.spawn(CellBundle::new(
-                    x,
-                    y,
-                    cell_id.0,
-                    child_net,
-                    CELL_SPRITE,
-                    &asset_server,
-                ));
             }
-            None => {}
+            None => brain.0.clone(),
+        };
+        child_net.mutate(&config.mutation_params(), &mut neat.innovations, rng);
+        let mut child_traits = match mate {
+            Some((_, (_, mate_traits), _)) => traits.crossover(mate_traits, rng),
+            None => *traits,
+        };
+        child_traits.mutate(&config, rng);
+        let child_species = neat.speciate(&child_net, species, &config);
+
+        cell_id.0 += 1;
+        num_cells += 1;
+
+        num_cells_spawned.0 += 1;
+        demographics.births += 1;
+        if config.reproduction_cost > 0.0 {
+            energy.0 -= config.reproduction_cost;
+            ledger.record(EnergyReason::Reproduction, -config.reproduction_cost);
         }
+        let mut child = commands.spawn(CellBundle::new(
+            x,
+            y,
+            cell_id.0,
+            child_net,
+            child_traits,
+            &config,
+            &clock,
+            rng,
+        ));
+        child.insert((Lineage(lineage.0), Ancestry::child_of(c.0, ancestry)));
+        if let Some(child_species) = child_species {
+            child.insert(child_species);
+        }
+        born_writer.send(CellBorn {
+            id: cell_id.0,
+            parent: Some(c.0),
+            lineage: lineage.0,
+            pos: vec2(x, y),
+        });
     }
 }
 
+/// Closest other cell, as of the start of the tick
+fn nearest_mate(cell_grid: &CellGrid, parent: Entity, pos: Vec2) -> Option<Entity> {
+    cell_grid
+        .0
+        .nearest(pos, f32::INFINITY, |c| c.entity != parent)
+        .map(|c| c.entity)
+}
+
 fn spawn_cells(
     mut commands: Commands,
+    clock: Res<SimClock>,
+    config: Res<SimConfig>,
+    mut rng: ResMut<SimRng>,
     mut cell_id: ResMut<CellId>,
-    asset_server: Res<AssetServer>,
+    mut demographics: ResMut<Demographics>,
+    mut neat: NeatState,
+    mut born_writer: EventWriter<CellBorn>,
     cell_query: Query<(With<Cell>, Without<UserControlledCell>)>,
 ) {
     let num_cells = cell_query.iter().len();
@@ -439,63 +635,78 @@ fn spawn_cells(
         return;
     }
 
-    let mut rng = rand::thread_rng();
-    for _ in 0..NUM_CELLS {
-        let x = rng.gen_range(-(W as f32) / 2.0..W as f32 / 2.0);
-        let y = rng.gen_range(-(H as f32) / 2.0..H as f32 / 2.0);
-        let net = Net::new(NET_ARCH.to_vec());
+    let rng = &mut rng.0;
+    let (w, h) = (config.world_width as f32, config.world_height as f32);
+    for _ in 0..config.num_cells {
+        let x = rng.gen_range(-w / 2.0..w / 2.0);
+        let y = rng.gen_range(-h / 2.0..h / 2.0);
+        let net = BrainNet::new(&config, &mut neat.innovations, rng);
+        let species = neat.speciate(&net, None, &config);
 
         cell_id.0 += 1;
-        commands.spawn(CellBundle::new(
-            x,
-            y,
-            cell_id.0,
-            net,
-            CELL_SPRITE,
-            &asset_server,
+        demographics.births += 1;
+        let traits = BodyTraits::from_config(&config);
+        let mut cell = commands.spawn(CellBundle::new(
+            x, y, cell_id.0, net, traits, &config, &clock, rng,
         ));
+        if let Some(species) = species {
+            cell.insert(species);
+        }
+        born_writer.send(CellBorn {
+            id: cell_id.0,
+            parent: None,
+            lineage: cell_id.0,
+            pos: vec2(x, y),
+        });
     }
 }
 
-fn calc_fitness(inp: [f64; NUM_INPUT_NODES], out: [f64; NUM_OUTPUT_NODES]) -> f32 {
-    // Inp
-    // 1 - dist between cell and target
-    // 2 - angle diff between cell and target
-    let dist = inp[0];
-    let target_angle = inp[1];
-    let cell_angle = inp[2];
-
-    // Out
-    // 0 & 1 - spin direction
-    // 0 > 1 - spin left
-    // 0 < 1 - spin right
-    // 2 - thrust when >= 0.7
-    // 3 - shoot when >= 0.7
-    let spin_l = out[0] > out[1];
-    let spin_r = out[1] > out[0];
-    let thrust = out[2] >= 0.7;
-    let shoot = out[3] >= 0.7;
-
-    let mut score = 0.0;
-    let scale = 1.0;
-
-    // Rules
-    if dist > 0.3 && thrust {
-        score += scale;
+impl BrainBuffers {
+    fn compile(&mut self, net: &BrainNet) {
+        let flat = net.to_flat();
+        self.values.resize(flat.num_values(), 0.0);
+        self.flat = Some(flat);
     }
-    if cell_angle < target_angle && spin_l {
-        score += scale;
-    } else if cell_angle > target_angle && spin_r {
-        score += scale;
+
+    /// Evaluates `inputs`, the value of every node ends up in `values`
+    fn run(&mut self) {
+        let flat = self.flat.as_ref().expect("brain compiled before it runs");
+        flat.predict_into(&self.inputs, &mut self.values);
+        self.is_fresh = true;
     }
-    if dist < 0.5 && shoot {
-        score += scale;
+
+    fn outputs(&self, num_outputs: usize) -> &[f32] {
+        &self.values[self.values.len() - num_outputs..]
     }
+}
 
-    (4.0 * scale) - score
+fn update_cell_grid(
+    mut cell_grid: ResMut<CellGrid>,
+    mut removed: RemovedComponents<Cell>,
+    moved_query: Query<
+        (Entity, &Cell, &Lineage, &Transform, &Velocity),
+        Or<(Changed<Transform>, Changed<Velocity>)>,
+    >,
+    cell_query: Query<(), With<Cell>>,
+) {
+    for entity in removed.iter() {
+        cell_grid.0.remove(entity);
+    }
+    for (entity, c, lineage, t, velocity) in moved_query.iter() {
+        let cell = SensedCell {
+            entity,
+            id: c.0,
+            lineage: lineage.0,
+            velocity: velocity.linvel,
+        };
+        cell_grid.0.insert(entity, t.translation.truncate(), cell);
+    }
+    cell_grid
+        .0
+        .prune(cell_query.iter().len(), |e| cell_query.contains(e));
 }
 
-fn angle_between(a: f32, b: f32, x: f32, y: f32) -> f32 {
+pub(super) fn angle_between(a: f32, b: f32, x: f32, y: f32) -> f32 {
     let angle_radians = (y - b).atan2(x - a);
     let mut angle_degrees = angle_radians.to_degrees();
     if angle_degrees < 0.0 {
@@ -1,45 +1,155 @@
-use std::time::{Duration, Instant};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use bevy::{prelude::*, time::common_conditions::on_timer, utils::HashMap};
+use crate::{
+    settings::DynamicSettings,
+    sim_config::SimConfig,
+    trackers::{on_sim_timer, FitnessScores, SimSet},
+    *,
+};
 
-use crate::{settings::DynamicSettings, trackers::FitnessScores, *};
-
-use super::{user::UserControlledCell, Cell};
+use super::{traits::BodyTraits, user::UserControlledCell};
 
 pub struct CellEnergyPlugin;
 
-#[derive(Resource)]
-pub struct EnergyMap(pub HashMap<u32, (f32, Instant)>);
+/// Energy of a cell, starts at `base_energy` and the cell dies once it runs out.
+/// The user controlled cell has one too but it never changes
+#[derive(Component, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Energy(pub f32);
+
+/// Why the energy of a cell changed
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum EnergyReason {
+    Food,
+    /// Fitness and body upkeep, every `ENERGY_UPDATE_INTERVAL_SECS`
+    Decay,
+    /// Bullets that expired without hitting anything
+    MissPenalty,
+    /// Drained by the kill rules, the no bullets rule unless custom ones are added
+    IdlePenalty,
+    /// Damage taken, and damage dealt plus kill bounties
+    Combat,
+    Reproduction,
+}
+
+/// Energy gained and spent by a cell since its birth, per reason
+#[derive(Component, Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EnergyLedger {
+    income: [f32; EnergyReason::ALL.len()],
+    /// Positive amounts
+    expense: [f32; EnergyReason::ALL.len()],
+}
+
+/// Ledgers of the cells that died, add the living ones for the population totals
+#[derive(Resource, Default, Clone)]
+pub struct PopulationLedger(pub EnergyLedger);
 
 impl Plugin for CellEnergyPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(EnergyMap(HashMap::new())).add_systems(
-            Update,
-            update_cell_energy.run_if(on_timer(Duration::from_secs_f32(
-                ENERGY_UPDATE_INTERVAL_SECS,
-            ))),
+        app.init_resource::<PopulationLedger>().add_systems(
+            FixedUpdate,
+            update_cell_energy
+                .run_if(on_sim_timer(ENERGY_UPDATE_INTERVAL_SECS))
+                .in_set(SimSet::Logic),
         );
     }
 }
 
 fn update_cell_energy(
-    mut energy_map: ResMut<EnergyMap>,
+    config: Res<SimConfig>,
     settings: Res<DynamicSettings>,
-    cell_query: Query<(&Cell, &FitnessScores), (With<Cell>, Without<UserControlledCell>)>,
+    mut cell_query: Query<
+        (&mut Energy, &mut EnergyLedger, &FitnessScores, &BodyTraits),
+        Without<UserControlledCell>,
+    >,
 ) {
-    for (cell, fitness) in cell_query.iter() {
-        match energy_map.0.get_mut(&cell.0) {
-            Some((v, i)) => {
-                *v -= settings.energy_decay_rate * fitness.get_fitness();
-                *i = Instant::now();
-            }
-            None => {
-                energy_map.0.insert(cell.0, (BASE_ENERGY, Instant::now()));
-            }
+    for (mut energy, mut ledger, fitness, traits) in cell_query.iter_mut() {
+        // Cheap bodies can make up for a bad fitness but never regain energy
+        let upkeep = config.trait_energy_cost * traits.upkeep(&config);
+        let decay = settings.energy_decay_rate * (fitness.get_fitness() + upkeep).max(0.0);
+        energy.0 -= decay;
+        ledger.record(EnergyReason::Decay, -decay);
+    }
+}
+
+impl Energy {
+    /// Adds `amount` up to `max_energy`, returns the energy actually gained
+    pub fn add(&mut self, amount: f32, max_energy: f32) -> f32 {
+        let before = self.0;
+        self.0 = max_energy.min(self.0 + amount);
+        self.0 - before
+    }
+}
+
+impl EnergyReason {
+    pub const ALL: [EnergyReason; 6] = [
+        EnergyReason::Food,
+        EnergyReason::Decay,
+        EnergyReason::MissPenalty,
+        EnergyReason::IdlePenalty,
+        EnergyReason::Combat,
+        EnergyReason::Reproduction,
+    ];
+
+    pub fn label(&self) -> &str {
+        match self {
+            EnergyReason::Food => "food",
+            EnergyReason::Decay => "decay",
+            EnergyReason::MissPenalty => "miss_penalty",
+            EnergyReason::IdlePenalty => "idle_penalty",
+            EnergyReason::Combat => "combat",
+            EnergyReason::Reproduction => "reproduction",
+        }
+    }
+}
+
+impl EnergyLedger {
+    /// Books `delta` as income when positive and as expense when negative
+    pub fn record(&mut self, reason: EnergyReason, delta: f32) {
+        if delta >= 0.0 {
+            self.income[reason as usize] += delta;
+        } else {
+            self.expense[reason as usize] -= delta;
+        }
+    }
+
+    pub fn income(&self, reason: EnergyReason) -> f32 {
+        self.income[reason as usize]
+    }
+
+    pub fn expense(&self, reason: EnergyReason) -> f32 {
+        self.expense[reason as usize]
+    }
+
+    pub fn net(&self, reason: EnergyReason) -> f32 {
+        self.income(reason) - self.expense(reason)
+    }
+
+    pub fn merge(&mut self, other: &EnergyLedger) {
+        for i in 0..EnergyReason::ALL.len() {
+            self.income[i] += other.income[i];
+            self.expense[i] += other.expense[i];
+        }
+    }
+
+    /// What was booked after `earlier`, a previous copy of this ledger
+    pub fn since(&self, earlier: &EnergyLedger) -> EnergyLedger {
+        let mut ledger = *self;
+        for i in 0..EnergyReason::ALL.len() {
+            ledger.income[i] -= earlier.income[i];
+            ledger.expense[i] -= earlier.expense[i];
         }
+        ledger
     }
+}
 
-    energy_map
-        .0
-        .retain(|_, (_, i)| i.elapsed().as_secs_f32() < 10.0);
+impl PopulationLedger {
+    /// Totals since the start of the run, `living` being the ledgers of the cells still alive
+    pub fn total<'a>(&self, living: impl Iterator<Item = &'a EnergyLedger>) -> EnergyLedger {
+        let mut total = self.0;
+        for ledger in living {
+            total.merge(ledger);
+        }
+        total
+    }
 }
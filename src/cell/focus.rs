@@ -2,20 +2,26 @@ use std::time::{Duration, Instant};
 
 use bevy::prelude::*;
 
-use crate::{
-    trackers::{BirthTs, FitnessScores, InstantTracker, NumCellsSpawned},
-    *,
-};
+use crate::brain::BrainGraph;
+use crate::trackers::{BirthTs, FitnessScores, NumCellsSpawned, SimClock};
 
-use super::{cell::Cell, energy::EnergyMap};
+use super::{
+    cell::Cell,
+    energy::{Energy, EnergyLedger},
+    traits::BodyTraits,
+};
 
 pub struct CellFocusPlugin;
 
 #[derive(Component)]
 pub struct FocusedCell;
 
+/// Brain of the focused cell and the node values of its last update
 #[derive(Resource, Default)]
-pub struct FocusedCellNet(pub Vec<Vec<f64>>);
+pub struct FocusedCellNet {
+    pub graph: BrainGraph,
+    pub values: Vec<f64>,
+}
 
 #[derive(Event)]
 pub struct UnFocusCellEvent(pub u32);
@@ -26,9 +32,12 @@ pub struct FocusedCellStats {
     pub score: f32,
     pub age: f32,
     pub pos: Vec2,
-    pub last_updated: InstantTracker,
+    /// Wall clock time, so that the selection survives a paused simulation
+    pub last_updated: Instant,
     pub num_cells_spawned: u32,
     pub fitness_score: f32,
+    pub traits: Option<BodyTraits>,
+    pub ledger: Option<EnergyLedger>,
 }
 
 impl Plugin for CellFocusPlugin {
@@ -42,53 +51,56 @@ impl Plugin for CellFocusPlugin {
 }
 
 fn update_focused_cell_stats(
+    clock: Res<SimClock>,
     mut stats: ResMut<FocusedCellStats>,
-    energy_map: Res<EnergyMap>,
     cells_query: Query<
         (
             &Cell,
+            &Energy,
             &BirthTs,
             &Transform,
             &NumCellsSpawned,
             &FitnessScores,
+            (&BodyTraits, &EnergyLedger),
         ),
         With<FocusedCell>,
     >,
 ) {
-    if let Some((c, birth_ts, transform, num_cells_spawned, fitness_score)) =
-        cells_query.iter().next()
+    if let Some((
+        c,
+        energy,
+        birth_ts,
+        transform,
+        num_cells_spawned,
+        fitness_score,
+        (traits, ledger),
+    )) = cells_query.iter().next()
     {
         let id = c.0;
-        let score = match energy_map.0.get(&c.0) {
-            Some((v, _)) => *v,
-            None => 0.0,
-        };
-        let age = birth_ts.0.elapsed();
+        let score = energy.0;
+        let age = birth_ts.0.elapsed(&clock);
         let pos = transform.translation.truncate();
         stats.id = id;
         stats.score = score;
         stats.age = age;
         stats.pos = pos;
-        stats.last_updated.set_instant_now();
+        stats.last_updated = Instant::now();
         stats.num_cells_spawned = num_cells_spawned.0;
         stats.fitness_score = fitness_score.get_fitness();
+        stats.traits = Some(*traits);
+        stats.ledger = Some(*ledger);
     }
 }
 
 fn update_focused_cell(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut focused_cell_query: Query<(&mut Handle<Image>, &Cell, Entity), With<FocusedCell>>,
+    focused_cell_query: Query<(&Cell, Entity), With<FocusedCell>>,
     mut reader: EventReader<UnFocusCellEvent>,
 ) {
-    for (mut image_handle, _, _) in focused_cell_query.iter_mut() {
-        *image_handle = asset_server.load(FOCUSED_CELL_SPRITE);
-    }
     for e in reader.iter() {
-        for (mut img_handle, cell, entity) in focused_cell_query.iter_mut() {
+        for (cell, entity) in focused_cell_query.iter() {
             if e.0 == cell.0 {
                 commands.entity(entity).remove::<FocusedCell>();
-                *img_handle = asset_server.load(CELL_SPRITE);
             }
         }
     }
@@ -101,17 +113,17 @@ impl FocusedCellStats {
             id: 0,
             pos: Vec2::ZERO,
             score: 0.0,
-            last_updated: InstantTracker(
-                Instant::now()
-                    .checked_sub(Duration::from_secs_f32(10.0))
-                    .unwrap(),
-            ),
+            last_updated: Instant::now()
+                .checked_sub(Duration::from_secs_f32(10.0))
+                .unwrap(),
             num_cells_spawned: 0,
             fitness_score: 1.0,
+            traits: None,
+            ledger: None,
         }
     }
 
     pub fn is_cell_focused(&self) -> bool {
-        !self.last_updated.elapsed_past(1.0)
+        self.last_updated.elapsed().as_secs_f32() < 1.0
     }
 }
@@ -0,0 +1,265 @@
+use std::{collections::HashMap, fmt::Write, fs, io, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::trackers::{BirthTs, SimClock, SimSet, SimTracker};
+
+use super::Cell;
+
+pub struct GenealogyPlugin;
+
+/// Parent and generation depth of a cell, founders have no parent and are generation 0
+#[derive(Component, Clone, Copy)]
+pub struct Ancestry {
+    pub parent: Option<u32>,
+    pub generation: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LineageRecord {
+    pub parent: Option<u32>,
+    pub generation: u32,
+    pub birth: SimTracker,
+    /// None while the cell is alive
+    pub death: Option<SimTracker>,
+    num_children: u32,
+}
+
+/// Family tree of the population, outlives the cells it records.
+/// Dead cells without any recorded descendants are dropped,
+/// so the store only grows with the ancestry of the living cells
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct Genealogy {
+    records: HashMap<u32, LineageRecord>,
+    #[serde(skip)]
+    entities: HashMap<Entity, u32>,
+}
+
+impl Plugin for GenealogyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Genealogy::default())
+            .add_systems(FixedUpdate, track_genealogy.in_set(SimSet::PostPhysics));
+    }
+}
+
+fn track_genealogy(
+    clock: Res<SimClock>,
+    mut genealogy: ResMut<Genealogy>,
+    mut removed: RemovedComponents<Cell>,
+    born_query: Query<(Entity, &Cell, &Ancestry, &BirthTs), Added<Cell>>,
+) {
+    for entity in removed.iter() {
+        genealogy.record_death(entity, clock.now());
+    }
+    for (entity, cell, ancestry, birth_ts) in born_query.iter() {
+        genealogy.record_birth(entity, cell.0, ancestry, birth_ts.0);
+    }
+}
+
+impl Ancestry {
+    pub fn founder() -> Self {
+        Self {
+            parent: None,
+            generation: 0,
+        }
+    }
+
+    pub fn child_of(parent_id: u32, parent: &Ancestry) -> Self {
+        Self {
+            parent: Some(parent_id),
+            generation: parent.generation + 1,
+        }
+    }
+}
+
+impl Genealogy {
+    pub fn get(&self, id: u32) -> Option<&LineageRecord> {
+        self.records.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The cell itself followed by its ancestors, back to the founder
+    pub fn ancestry(&self, id: u32) -> Vec<(u32, &LineageRecord)> {
+        let mut chain = Vec::new();
+        let mut next = Some(id);
+        while let Some(id) = next {
+            let Some(record) = self.records.get(&id) else {
+                break;
+            };
+            chain.push((id, record));
+            next = record.parent;
+        }
+
+        chain
+    }
+
+    /// Replaces the records with the ones from a snapshot,
+    /// the restored cells are matched again as they get spawned
+    pub fn restore(&mut self, saved: Genealogy) {
+        self.records = saved.records;
+        self.entities.clear();
+    }
+
+    fn record_birth(&mut self, entity: Entity, id: u32, ancestry: &Ancestry, birth: SimTracker) {
+        self.entities.insert(entity, id);
+        if self.records.contains_key(&id) {
+            return;
+        }
+
+        if let Some(parent) = ancestry.parent.and_then(|p| self.records.get_mut(&p)) {
+            parent.num_children += 1;
+        }
+        self.records.insert(
+            id,
+            LineageRecord {
+                parent: ancestry.parent,
+                generation: ancestry.generation,
+                birth,
+                death: None,
+                num_children: 0,
+            },
+        );
+    }
+
+    fn record_death(&mut self, entity: Entity, death: SimTracker) {
+        let Some(id) = self.entities.remove(&entity) else {
+            return;
+        };
+        if let Some(record) = self.records.get_mut(&id) {
+            record.death = Some(death);
+        }
+
+        // Walk up the dead branch, dropping ancestors left without descendants
+        let mut next = Some(id);
+        while let Some(id) = next {
+            match self.records.get(&id) {
+                Some(r) if r.death.is_some() && r.num_children == 0 => {
+                    next = r.parent;
+                    self.records.remove(&id);
+                    if let Some(parent) = next.and_then(|p| self.records.get_mut(&p)) {
+                        parent.num_children -= 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Graphviz digraph with an edge from every parent to its children,
+    /// dead cells are drawn dashed
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph genealogy {\n    node [shape=box];\n");
+        for id in self.sorted_ids() {
+            let r = &self.records[&id];
+            let mut label = format!(
+                "{}\\ngen {}\\nborn {:.1}s",
+                id,
+                r.generation,
+                r.birth.secs()
+            );
+            let mut style = "";
+            if let Some(death) = r.death {
+                let _ = write!(label, "\\ndied {:.1}s", death.secs());
+                style = ", style=dashed";
+            }
+            let _ = writeln!(out, "    c{} [label=\"{}\"{}];", id, label, style);
+        }
+        for id in self.sorted_ids() {
+            if let Some(parent) = self.records[&id].parent {
+                if self.records.contains_key(&parent) {
+                    let _ = writeln!(out, "    c{} -> c{};", parent, id);
+                }
+            }
+        }
+        out.push_str("}\n");
+
+        out
+    }
+
+    /// One Newick tree per founder, one per line.
+    /// Branch lengths are the seconds between the births of a parent and its child
+    pub fn to_newick(&self) -> String {
+        enum Visit {
+            Enter(u32),
+            Close(u32),
+            Comma,
+        }
+
+        let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut roots = Vec::new();
+        for id in self.sorted_ids() {
+            match self.records[&id].parent {
+                Some(p) if self.records.contains_key(&p) => children.entry(p).or_default().push(id),
+                _ => roots.push(id),
+            }
+        }
+
+        // Iterative, the trees can be thousands of generations deep
+        let mut out = String::new();
+        for root in roots {
+            let mut stack = vec![Visit::Enter(root)];
+            while let Some(visit) = stack.pop() {
+                match visit {
+                    Visit::Enter(id) => match children.get(&id) {
+                        Some(kids) => {
+                            out.push('(');
+                            stack.push(Visit::Close(id));
+                            for (i, kid) in kids.iter().enumerate().rev() {
+                                stack.push(Visit::Enter(*kid));
+                                if i > 0 {
+                                    stack.push(Visit::Comma);
+                                }
+                            }
+                        }
+                        None => self.write_newick_label(&mut out, id),
+                    },
+                    Visit::Close(id) => {
+                        out.push(')');
+                        self.write_newick_label(&mut out, id);
+                    }
+                    Visit::Comma => out.push(','),
+                }
+            }
+            out.push_str(";\n");
+        }
+
+        out
+    }
+
+    pub fn save_dot<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        write_file(path, &self.to_dot())
+    }
+
+    pub fn save_newick<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        write_file(path, &self.to_newick())
+    }
+
+    fn write_newick_label(&self, out: &mut String, id: u32) {
+        let r = &self.records[&id];
+        let _ = write!(out, "c{}", id);
+        if let Some(parent) = r.parent.and_then(|p| self.records.get(&p)) {
+            let _ = write!(out, ":{:.2}", r.birth.secs() - parent.birth.secs());
+        }
+    }
+
+    fn sorted_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.records.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+fn write_file<P: AsRef<Path>>(path: P, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.as_ref().parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)
+}
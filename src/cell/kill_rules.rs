@@ -0,0 +1,323 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boundary::Boundary,
+    events::CellDied,
+    settings::DynamicSettings,
+    sim_config::SimConfig,
+    stats::DeathCause,
+    trackers::{on_sim_timer, BirthPlace, BirthTs, LastBulletFired, SimClock, SimSet},
+    *,
+};
+
+use super::{
+    energy::{Energy, EnergyLedger, EnergyReason, PopulationLedger},
+    user::UserControlledCell,
+    Cell, KilledInCombat,
+};
+
+/// Culls the cells that ran out of energy or broke one of the kill rules
+pub struct KillRulesPlugin;
+
+/// What a kill rule gets to see of a cell
+pub struct CellVitals {
+    pub id: u32,
+    /// Seconds since birth
+    pub age: f32,
+    pub energy: f32,
+    /// From the birth place to the current position, across the edges of a wrapped world
+    pub displacement: Vec2,
+    /// Seconds since the last shot, or since birth
+    pub since_last_shot: f32,
+}
+
+pub enum Verdict {
+    Spare,
+    Kill(DeathCause),
+    /// Takes energy away, the cell dies of the given cause if that empties it
+    Drain(f32, DeathCause),
+}
+
+/// Culling heuristic, checked every half second on every cell that still has energy.
+/// The first rule that kills a cell decides its `DeathCause`
+pub trait KillRule: Send + Sync + 'static {
+    fn judge(&self, cell: &CellVitals) -> Verdict;
+}
+
+/// Rules added by library users, checked after the built-in ones
+#[derive(Resource, Default)]
+pub struct CustomKillRules(Vec<Box<dyn KillRule>>);
+
+/// Lets library users plug in their own kill rules, cells they kill die of `DeathCause::Culled`
+pub trait RegisterKillRule {
+    fn register_kill_rule(&mut self, rule: impl KillRule) -> &mut Self;
+}
+
+/// The built-in rules and their parameters.
+/// Running out of energy always kills, it isn't one of them
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KillRulesConfig {
+    pub idle: IdleRule,
+    pub revolving: RevolvingRule,
+    pub one_direction: OneDirectionRule,
+    pub no_bullets: NoBulletsRule,
+}
+
+/// Kills cells still close to their birth place between `from_secs` and `until_secs` of age
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdleRule {
+    pub is_enabled: bool,
+    pub from_secs: f32,
+    pub until_secs: f32,
+    pub min_distance: f32,
+}
+
+/// Same as `IdleRule` later in life, further out, catches cells circling in place
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RevolvingRule {
+    pub is_enabled: bool,
+    pub from_secs: f32,
+    pub until_secs: f32,
+    pub min_distance: f32,
+}
+
+/// Kills cells that moved `max_axis_ratio` times further along one axis than the other
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OneDirectionRule {
+    pub is_enabled: bool,
+    pub from_secs: f32,
+    pub until_secs: f32,
+    pub max_axis_ratio: f32,
+}
+
+/// Drains `penalty` energy on every check once a cell went `after_secs` without shooting
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoBulletsRule {
+    pub is_enabled: bool,
+    pub after_secs: f32,
+    pub penalty: f32,
+}
+
+impl Plugin for KillRulesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CustomKillRules>()
+            .add_event::<CellDied>()
+            .add_systems(
+                FixedUpdate,
+                kill_bad_cells
+                    .run_if(on_sim_timer(KILL_RULES_INTERVAL_SECS))
+                    .in_set(SimSet::Logic),
+            );
+    }
+}
+
+impl RegisterKillRule for App {
+    fn register_kill_rule(&mut self, rule: impl KillRule) -> &mut Self {
+        self.init_resource::<CustomKillRules>();
+        self.world
+            .resource_mut::<CustomKillRules>()
+            .0
+            .push(Box::new(rule));
+        self
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn kill_bad_cells(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    config: Res<SimConfig>,
+    settings: Res<DynamicSettings>,
+    custom_rules: Res<CustomKillRules>,
+    mut population_ledger: ResMut<PopulationLedger>,
+    mut writer: EventWriter<CellDied>,
+    mut cell_query: Query<
+        (
+            Entity,
+            &Cell,
+            (&mut Energy, &mut EnergyLedger),
+            &Transform,
+            &BirthPlace,
+            &BirthTs,
+            &LastBulletFired,
+            Option<&KilledInCombat>,
+        ),
+        Without<UserControlledCell>,
+    >,
+) {
+    let boundary = Boundary::new(&config);
+    let rules: Vec<&dyn KillRule> = settings
+        .kill_rules
+        .enabled()
+        .into_iter()
+        .chain(custom_rules.0.iter().map(|r| r.as_ref()))
+        .collect();
+
+    for (
+        entity,
+        c,
+        (mut energy, mut ledger),
+        transform,
+        birth_place,
+        birth_ts,
+        last_bullet_fired,
+        killed,
+    ) in cell_query.iter_mut()
+    {
+        let mut vitals = CellVitals {
+            id: c.0,
+            age: birth_ts.0.elapsed(&clock),
+            energy: energy.0,
+            displacement: boundary.offset(birth_place.0, transform.translation.truncate()),
+            since_last_shot: last_bullet_fired.0.elapsed(&clock),
+        };
+
+        let mut cause = match (vitals.energy <= 0.0, killed) {
+            (true, Some(_)) => Some(DeathCause::Killed),
+            (true, None) => Some(DeathCause::Starved),
+            _ => None,
+        };
+        for rule in rules.iter() {
+            if cause.is_some() {
+                break;
+            }
+            match rule.judge(&vitals) {
+                Verdict::Spare => {}
+                Verdict::Kill(c) => cause = Some(c),
+                Verdict::Drain(amount, drained_cause) => {
+                    energy.0 -= amount;
+                    ledger.record(EnergyReason::IdlePenalty, -amount);
+                    vitals.energy = energy.0;
+                    if vitals.energy <= 0.0 {
+                        cause = Some(drained_cause);
+                    }
+                }
+            }
+        }
+
+        let Some(cause) = cause else {
+            continue;
+        };
+        commands.entity(entity).despawn();
+        population_ledger.0.merge(&ledger);
+        writer.send(CellDied {
+            id: vitals.id,
+            cause,
+            age: vitals.age,
+            energy: vitals.energy,
+        });
+    }
+}
+
+impl KillRulesConfig {
+    /// Built-in rules that are turned on, in the order they are checked
+    pub fn enabled(&self) -> Vec<&dyn KillRule> {
+        let rules: [(bool, &dyn KillRule); 4] = [
+            (self.idle.is_enabled, &self.idle),
+            (self.revolving.is_enabled, &self.revolving),
+            (self.one_direction.is_enabled, &self.one_direction),
+            (self.no_bullets.is_enabled, &self.no_bullets),
+        ];
+        rules
+            .into_iter()
+            .filter(|(is_enabled, _)| *is_enabled)
+            .map(|(_, rule)| rule)
+            .collect()
+    }
+}
+
+fn is_within(age: f32, from_secs: f32, until_secs: f32) -> bool {
+    age >= from_secs && age < until_secs
+}
+
+impl KillRule for IdleRule {
+    fn judge(&self, cell: &CellVitals) -> Verdict {
+        if is_within(cell.age, self.from_secs, self.until_secs)
+            && cell.displacement.length() < self.min_distance
+        {
+            return Verdict::Kill(DeathCause::Idle);
+        }
+        Verdict::Spare
+    }
+}
+
+impl KillRule for RevolvingRule {
+    fn judge(&self, cell: &CellVitals) -> Verdict {
+        if is_within(cell.age, self.from_secs, self.until_secs)
+            && cell.displacement.length() < self.min_distance
+        {
+            return Verdict::Kill(DeathCause::Revolving);
+        }
+        Verdict::Spare
+    }
+}
+
+impl KillRule for OneDirectionRule {
+    fn judge(&self, cell: &CellVitals) -> Verdict {
+        let Vec2 { x, y } = cell.displacement.abs();
+        if is_within(cell.age, self.from_secs, self.until_secs)
+            && (x * self.max_axis_ratio < y || y * self.max_axis_ratio < x)
+        {
+            return Verdict::Kill(DeathCause::OneDirection);
+        }
+        Verdict::Spare
+    }
+}
+
+impl KillRule for NoBulletsRule {
+    fn judge(&self, cell: &CellVitals) -> Verdict {
+        if cell.since_last_shot >= self.after_secs {
+            return Verdict::Drain(self.penalty, DeathCause::NoBullets);
+        }
+        Verdict::Spare
+    }
+}
+
+impl Default for IdleRule {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            from_secs: IDLE_RULE_FROM_SECS,
+            until_secs: IDLE_RULE_UNTIL_SECS,
+            min_distance: IDLE_RULE_MIN_DISTANCE,
+        }
+    }
+}
+
+impl Default for RevolvingRule {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            from_secs: REVOLVING_RULE_FROM_SECS,
+            until_secs: REVOLVING_RULE_UNTIL_SECS,
+            min_distance: REVOLVING_RULE_MIN_DISTANCE,
+        }
+    }
+}
+
+impl Default for OneDirectionRule {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            from_secs: ONE_DIRECTION_RULE_FROM_SECS,
+            until_secs: ONE_DIRECTION_RULE_UNTIL_SECS,
+            max_axis_ratio: ONE_DIRECTION_RULE_MAX_AXIS_RATIO,
+        }
+    }
+}
+
+impl Default for NoBulletsRule {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            after_secs: NO_BULLETS_RULE_AFTER_SECS,
+            penalty: NO_BULLET_PENALTY,
+        }
+    }
+}
@@ -1,7 +1,11 @@
-mod bundle;
+pub mod bundle;
 mod cell;
 pub mod energy;
 pub mod focus;
+pub mod genealogy;
+pub mod kill_rules;
+pub mod sensors;
+pub mod traits;
 pub mod user;
 
 pub use cell::*;
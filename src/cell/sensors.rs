@@ -0,0 +1,213 @@
+use bevy::{ecs::system::SystemParam, math::vec2, prelude::*};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    boundary::{Boundary, BoundaryMode},
+    bullet::{Bullet, BulletGrid},
+    food::Food,
+    sim_config::SimConfig,
+    *,
+};
+
+use super::cell::{angle_between, Cell, CellGrid};
+
+/// Inputs used when nothing is in sight
+const NOTHING_SENSED: [f64; 3] = [1.0, 0.0, 0.0];
+
+/// Distance, relative angle and closing velocity of the nearest cell of
+/// another lineage within `vision_radius`
+#[allow(clippy::too_many_arguments)]
+pub fn sense_nearest_enemy(
+    config: &SimConfig,
+    cell_grid: &CellGrid,
+    pos: Vec2,
+    velocity: Vec2,
+    heading: f32,
+    vision_radius: f32,
+    cell_id: u32,
+    lineage: u32,
+) -> [f64; 3] {
+    Boundary::new(config)
+        .nearest(&cell_grid.0, pos, vision_radius, |c| {
+            c.item.id != cell_id && c.item.lineage != lineage
+        })
+        .map(|(c, target)| {
+            let body = (target, c.item.velocity);
+            sense_body(config, pos, velocity, heading, vision_radius, body)
+        })
+        .unwrap_or(NOTHING_SENSED)
+}
+
+/// Distance, relative angle and closing velocity of the nearest bullet fired
+/// by someone else within `vision_radius`
+pub fn sense_nearest_bullet(
+    config: &SimConfig,
+    bullet_grid: &BulletGrid,
+    pos: Vec2,
+    velocity: Vec2,
+    heading: f32,
+    vision_radius: f32,
+    cell_id: u32,
+) -> [f64; 3] {
+    Boundary::new(config)
+        .nearest(&bullet_grid.0, pos, vision_radius, |b| {
+            b.item.owner != cell_id
+        })
+        .map(|(b, target)| {
+            let body = (target, b.item.velocity);
+            sense_body(config, pos, velocity, heading, vision_radius, body)
+        })
+        .unwrap_or(NOTHING_SENSED)
+}
+
+/// Energy left, scaled to `0..=1`
+pub fn sense_energy(config: &SimConfig, energy: f32) -> f64 {
+    (energy / config.max_energy).clamp(0.0, 1.0) as f64
+}
+
+/// Distance to the nearest edge of the arena scaled by the vision radius,
+/// 1 when it's out of sight or there are no walls
+pub fn sense_wall(config: &SimConfig, pos: Vec2, vision_radius: f32) -> f64 {
+    let dist = Boundary::new(config).wall_distance(pos) / vision_radius;
+    dist.min(1.0) as f64
+}
+
+/// `heading` is the cell's angle scaled to `0..1`, as fed to the brain,
+/// `body` the position and velocity of what is sensed
+fn sense_body(
+    config: &SimConfig,
+    pos: Vec2,
+    velocity: Vec2,
+    heading: f32,
+    vision_radius: f32,
+    (target, target_velocity): (Vec2, Vec2),
+) -> [f64; 3] {
+    let dist = pos.distance(target) / vision_radius;
+    if dist > 1.0 {
+        return NOTHING_SENSED;
+    }
+
+    let angle = angle_between(pos.x, pos.y, target.x, target.y);
+    let relative_angle = (angle - heading * 360.0).rem_euclid(360.0) / 360.0;
+
+    // Positive when the two are getting closer
+    let direction = (target - pos).normalize_or_zero();
+    let closing_velocity =
+        ((velocity - target_velocity).dot(direction) / config.bullet_speed).clamp(-1.0, 1.0);
+
+    [dist as f64, relative_angle as f64, closing_velocity as f64]
+}
+
+/// What a vision ray ran into
+#[derive(Clone, Copy)]
+pub enum VisionHit {
+    Nothing,
+    Food,
+    Cell,
+    Bullet,
+    /// Anything that isn't food, a cell or a bullet
+    Wall,
+}
+
+/// Casts `num_vision_rays` rays spread across `vision_fov_degrees`,
+/// when wrapping a ray leaving through an edge goes on from the opposite one
+#[derive(SystemParam)]
+pub struct Vision<'w, 's> {
+    config: Res<'w, SimConfig>,
+    rapier_context: Res<'w, RapierContext>,
+    food_query: Query<'w, 's, (), With<Food>>,
+    cell_query: Query<'w, 's, (), With<Cell>>,
+    bullet_query: Query<'w, 's, (), With<Bullet>>,
+}
+
+impl<'w, 's> Vision<'w, 's> {
+    /// Hit distance and hit type for each ray, `angle` is the cell's facing
+    /// direction in radians
+    pub fn sense(&self, entity: Entity, pos: Vec2, angle: f32, radius: f32) -> Vec<f64> {
+        let num_rays = self.config.num_vision_rays;
+        let fov = self.config.vision_fov_degrees.to_radians();
+        let boundary = Boundary::new(&self.config);
+        let filter = QueryFilter::new()
+            .exclude_sensors()
+            .exclude_collider(entity);
+
+        let mut inputs = Vec::with_capacity(num_rays * 2);
+        for i in 0..num_rays {
+            let offset = if num_rays == 1 {
+                0.0
+            } else {
+                -fov / 2.0 + fov * i as f32 / (num_rays - 1) as f32
+            };
+            let ray_angle = angle + offset;
+            let direction = vec2(ray_angle.cos(), ray_angle.sin());
+
+            let (dist, hit) = match self.cast_ray(&boundary, pos, direction, radius, filter) {
+                Some((e, toi)) => (toi / radius, self.classify(e)),
+                None => (1.0, VisionHit::Nothing),
+            };
+            inputs.push(dist as f64);
+            inputs.push(hit.value());
+        }
+
+        inputs
+    }
+
+    /// First hit within `radius` and how far along the ray it is.
+    /// Rapier knows nothing of the wrapping, so the ray is cast one leg per crossed edge
+    fn cast_ray(
+        &self,
+        boundary: &Boundary,
+        pos: Vec2,
+        direction: Vec2,
+        radius: f32,
+        filter: QueryFilter,
+    ) -> Option<(Entity, f32)> {
+        let mut origin = pos;
+        let mut travelled = 0.0;
+        for _ in 0..VISION_MAX_RAY_LEGS {
+            let remaining = radius - travelled;
+            let leg = match boundary.mode {
+                BoundaryMode::Wrap => boundary.edge_distance(origin, direction).min(remaining),
+                _ => remaining,
+            };
+            if let Some((e, toi)) = self
+                .rapier_context
+                .cast_ray(origin, direction, leg, true, filter)
+            {
+                return Some((e, travelled + toi));
+            }
+
+            if leg >= remaining {
+                break;
+            }
+            travelled += leg;
+            origin = boundary.reenter(origin + direction * leg, direction);
+        }
+
+        None
+    }
+
+    fn classify(&self, entity: Entity) -> VisionHit {
+        if self.food_query.contains(entity) {
+            VisionHit::Food
+        } else if self.cell_query.contains(entity) {
+            VisionHit::Cell
+        } else if self.bullet_query.contains(entity) {
+            VisionHit::Bullet
+        } else {
+            VisionHit::Wall
+        }
+    }
+}
+
+impl VisionHit {
+    fn value(self) -> f64 {
+        match self {
+            VisionHit::Nothing => 0.0,
+            VisionHit::Food => 0.25,
+            VisionHit::Cell => 0.5,
+            VisionHit::Bullet => 0.75,
+            VisionHit::Wall => 1.0,
+        }
+    }
+}
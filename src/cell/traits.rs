@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{sim_config::SimConfig, *};
+
+/// Physical make-up of a cell, inherited and mutated along with its brain.
+/// Founders start with the config defaults
+#[derive(Component, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BodyTraits {
+    /// Thrust force
+    pub speed: f32,
+    pub vision_radius: f32,
+    /// Seconds between two shots
+    pub fire_rate: f32,
+    /// Collider radius
+    pub size: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BodyTrait {
+    Speed,
+    Vision,
+    FireRate,
+    Size,
+}
+
+impl BodyTrait {
+    pub const ALL: [BodyTrait; 4] = [
+        BodyTrait::Speed,
+        BodyTrait::Vision,
+        BodyTrait::FireRate,
+        BodyTrait::Size,
+    ];
+
+    pub fn label(&self) -> &str {
+        match self {
+            BodyTrait::Speed => "Speed",
+            BodyTrait::Vision => "Vision",
+            BodyTrait::FireRate => "Fire Rate",
+            BodyTrait::Size => "Size",
+        }
+    }
+}
+
+impl BodyTraits {
+    pub fn from_config(config: &SimConfig) -> Self {
+        Self {
+            speed: config.cell_speed,
+            vision_radius: config.vision_radius,
+            fire_rate: config.bullet_fire_rate,
+            size: config.cell_size,
+        }
+    }
+
+    pub fn get(&self, t: BodyTrait) -> f32 {
+        match t {
+            BodyTrait::Speed => self.speed,
+            BodyTrait::Vision => self.vision_radius,
+            BodyTrait::FireRate => self.fire_rate,
+            BodyTrait::Size => self.size,
+        }
+    }
+
+    fn get_mut(&mut self, t: BodyTrait) -> &mut f32 {
+        match t {
+            BodyTrait::Speed => &mut self.speed,
+            BodyTrait::Vision => &mut self.vision_radius,
+            BodyTrait::FireRate => &mut self.fire_rate,
+            BodyTrait::Size => &mut self.size,
+        }
+    }
+
+    /// Value of the trait as a multiple of its config default, 1 for founders
+    pub fn relative(&self, t: BodyTrait, config: &SimConfig) -> f32 {
+        let default = BodyTraits::from_config(config).get(t);
+        if default == 0.0 {
+            return 1.0;
+        }
+        self.get(t) / default
+    }
+
+    /// Each trait comes from either parent
+    pub fn crossover(&self, other: &BodyTraits, rng: &mut impl Rng) -> Self {
+        let mut child = *self;
+        for t in BodyTrait::ALL {
+            if rng.gen_bool(0.5) {
+                *child.get_mut(t) = other.get(t);
+            }
+        }
+        child
+    }
+
+    /// Scales traits by up to `trait_mutation_variation` either way,
+    /// within `TRAIT_MIN_SCALE..=TRAIT_MAX_SCALE` of the defaults
+    pub fn mutate(&mut self, config: &SimConfig, rng: &mut impl Rng) {
+        if config.trait_mutation_rate <= 0.0 {
+            return;
+        }
+
+        let defaults = BodyTraits::from_config(config);
+        let variation = config.trait_mutation_variation;
+        for t in BodyTrait::ALL {
+            if !rng.gen_bool(config.trait_mutation_rate as f64) {
+                continue;
+            }
+            let default = defaults.get(t);
+            let value = self.get_mut(t);
+            *value *= 1.0 + rng.gen_range(-variation..=variation);
+            *value = value.clamp(default * TRAIT_MIN_SCALE, default * TRAIT_MAX_SCALE);
+        }
+    }
+
+    /// Energy cost of running the body on top of the fitness, in the same units.
+    /// Every trait costs as much as it is above its default and saves as much below it,
+    /// a shorter time between shots costs more
+    pub fn upkeep(&self, config: &SimConfig) -> f32 {
+        BodyTrait::ALL
+            .iter()
+            .map(|t| {
+                let relative = self.relative(*t, config);
+                match t {
+                    BodyTrait::FireRate => 1.0 / relative - 1.0,
+                    _ => relative - 1.0,
+                }
+            })
+            .sum()
+    }
+
+    /// Bullet damage scaled down for bigger cells
+    pub fn damage_taken(&self, damage: f32, config: &SimConfig) -> f32 {
+        damage / self.relative(BodyTrait::Size, config)
+    }
+
+    pub fn sprite_scale(&self, config: &SimConfig) -> f32 {
+        CELL_SPRITE_SCALE * self.relative(BodyTrait::Size, config)
+    }
+}
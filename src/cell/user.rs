@@ -8,15 +8,21 @@ use bevy_prototype_debug_lines::{DebugLines, DebugLinesPlugin, DebugShapes};
 use bevy_rapier2d::prelude::*;
 
 use crate::{
-    food::FoodTree,
-    nn::Net,
-    trackers::{LastBulletFired, LastUpdated, OneSecondTimer, PeriodicUpdateInterval},
+    boundary::Boundary,
+    brain::BrainNet,
+    events::BulletFired,
+    food::FoodGrid,
+    neat::NeatState,
+    rng::SimRng,
+    sim_config::SimConfig,
+    trackers::{LastBulletFired, LastUpdated, OneSecondTimer, PeriodicUpdateInterval, SimClock},
     *,
 };
 
 use super::{
     bundle::CellBundle,
     cell::{perform_cell_action, CellAction},
+    traits::BodyTraits,
 };
 
 pub struct UserCellPlugin;
@@ -27,38 +33,50 @@ pub struct UserControlledCell;
 impl Plugin for UserCellPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(DebugLinesPlugin::default())
+            .add_event::<BulletFired>()
             .add_systems(Startup, setup)
             .add_systems(Update, update_user_controlled_cell);
     }
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    config: Res<SimConfig>,
+    mut rng: ResMut<SimRng>,
+    mut neat: NeatState,
+) {
     if !IS_USER_ENABLED {
         return;
     }
 
-    let net = Net::new(NET_ARCH.to_vec());
+    let net = BrainNet::new(&config, &mut neat.innovations, &mut rng.0);
+    let traits = BodyTraits::from_config(&config);
     commands.spawn((
-        CellBundle::new(0.0, 0.0, 0, net, USER_CELL_SPRITE, &asset_server),
+        CellBundle::new(0.0, 0.0, 0, net, traits, &config, &clock, &mut rng.0),
         UserControlledCell,
     ));
 }
 
 fn update_user_controlled_cell(
     mut commands: Commands,
+    clock: Res<SimClock>,
+    config: Res<SimConfig>,
     second_timer: Res<OneSecondTimer>,
-    food_tree: Res<FoodTree>,
+    food_grid: Res<FoodGrid>,
     keyboard_input: Res<Input<KeyCode>>,
-    asset_server: Res<AssetServer>,
     mut lines: ResMut<DebugLines>,
     mut shapes: ResMut<DebugShapes>,
+    mut fired_writer: EventWriter<BulletFired>,
     mut user_query: Query<
         (
+            Entity,
             &mut Transform,
             &mut ExternalForce,
             &mut LastUpdated,
             &mut LastBulletFired,
             &PeriodicUpdateInterval,
+            &BodyTraits,
         ),
         With<UserControlledCell>,
     >,
@@ -68,11 +86,13 @@ fn update_user_controlled_cell(
     }
 
     let (
+        entity,
         mut transform,
         mut external_force,
         mut last_updated,
         mut last_bullet_fired,
         periodic_update_interval,
+        traits,
     ) = user_query.get_single_mut().unwrap();
     let w_key = keyboard_input.pressed(KeyCode::W);
     let a_key = keyboard_input.pressed(KeyCode::A);
@@ -84,31 +104,31 @@ fn update_user_controlled_cell(
     shapes
         .circle()
         .position(transform.translation)
-        .radius(VISION_RADIUS)
+        .radius(traits.vision_radius)
         .color(Color::RED);
 
-    if last_updated.0.elapsed_within(UPDATE_INTERVAL) {
+    if last_updated
+        .0
+        .elapsed_within(&clock, config.update_interval)
+    {
         return;
     }
-    if second_timer.0.elapsed_within(periodic_update_interval.0) {
+    if second_timer
+        .0
+        .elapsed_within(&clock, periodic_update_interval.0)
+    {
         return;
     }
 
-    last_updated.0.set_instant_now();
+    last_updated.0.set_now(&clock);
     let mut target_x = 0.0;
     let mut target_y = 0.0;
-    let key = (transform.translation.x, transform.translation.y);
-    if let Some(t) = &food_tree.0 {
-        match t.nearest(&[key.0 as f32, key.1 as f32]) {
-            Some(v) => {
-                if v.squared_distance <= VISION_RADIUS * VISION_RADIUS {
-                    let [x, y] = v.item;
-                    target_x = *x;
-                    target_y = *y;
-                }
-            }
-            None => {}
-        }
+    let pos = transform.translation.truncate();
+    if let Some((_, food_pos)) =
+        Boundary::new(&config).nearest(&food_grid.0, pos, traits.vision_radius, |_| true)
+    {
+        target_x = food_pos.x;
+        target_y = food_pos.y;
     }
     lines.line_colored(
         transform.translation,
@@ -128,22 +148,28 @@ fn update_user_controlled_cell(
         spin_right,
         shoot,
     };
-    perform_cell_action(
+    let fired = perform_cell_action(
         action,
         0,
+        entity,
         &mut last_bullet_fired,
         &mut external_force,
         &mut commands,
         &mut transform,
-        &asset_server,
+        traits,
+        &clock,
+        &config,
     );
+    if let Some(fired) = fired {
+        fired_writer.send(fired);
+    }
 
     // This is for debug prints
     let nn_inp_dist = transform
         .translation
         .truncate()
         .distance(vec2(target_x as f32, target_y as f32))
-        / VISION_RADIUS;
+        / traits.vision_radius;
     let nn_inp_dist = if nn_inp_dist > 1.0 { 1.0 } else { nn_inp_dist };
 
     let nn_inp_angle = angle_between(
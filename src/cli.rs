@@ -0,0 +1,90 @@
+use std::process;
+
+use crate::sim_config::SimConfig;
+
+const USAGE: &str = "\
+Options:
+  --config <path>         Load the simulation config from a RON file
+  --set <key>=<value>     Override a single config field, can be repeated
+  --seed <n>              Shorthand for --set seed=<n>
+  --resume <path>         Restore a previously saved snapshot
+  --record <path>         Record stats to a .csv or .jsonl file
+  --dump-config [path]    Write the config to a file, or stdout, and exit
+  --help                  Print this message";
+
+/// Command line flags shared by the windowed and headless binaries
+pub struct CliArgs {
+    pub config: SimConfig,
+    pub resume_from: Option<String>,
+}
+
+impl CliArgs {
+    /// Parses the process arguments, exits with a message on bad flags or an invalid config
+    pub fn parse() -> Self {
+        match Self::from_args(std::env::args().skip(1)) {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("error: {}\n\n{}", e, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    /// Parses flags without the program name, the config is loaded, overridden and validated
+    pub fn from_args(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.peekable();
+        let mut config_path = None;
+        let mut overrides = Vec::new();
+        let mut resume_from = None;
+        let mut dump_config = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = |flag: &str| {
+                args.next()
+                    .ok_or_else(|| format!("{} expects a value", flag))
+            };
+            match arg.as_str() {
+                "--config" => config_path = Some(value("--config")?),
+                "--set" => overrides.push(value("--set")?),
+                "--seed" => overrides.push(format!("seed={}", value("--seed")?)),
+                "--resume" => resume_from = Some(value("--resume")?),
+                "--record" => overrides.push(format!("stats_file=Some({:?})", value("--record")?)),
+                "--dump-config" => {
+                    let path = args.next_if(|a| !a.starts_with("--"));
+                    dump_config = Some(path);
+                }
+                "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ => return Err(format!("unknown flag {}", arg)),
+            }
+        }
+
+        let mut config = match &config_path {
+            Some(path) => {
+                SimConfig::load(path).map_err(|e| format!("failed to load {}: {}", path, e))?
+            }
+            None => SimConfig::default(),
+        };
+        for pair in overrides.iter() {
+            config.set(pair).map_err(|e| e.to_string())?;
+        }
+        config.validate().map_err(|e| e.to_string())?;
+
+        if let Some(path) = dump_config {
+            match path {
+                Some(path) => config
+                    .save(&path)
+                    .map_err(|e| format!("failed to write {}: {}", path, e))?,
+                None => println!("{}", config.to_ron()),
+            }
+            process::exit(0);
+        }
+
+        Ok(Self {
+            config,
+            resume_from,
+        })
+    }
+}
@@ -1,22 +1,46 @@
+// The tuning knobs below are only defaults for `SimConfig`,
+// see `--dump-config` to override them without a recompile
+
 // Windowing
 pub const WW: usize = 900;
 pub const WH: usize = 700;
 pub const BG_COLOR: (u8, u8, u8) = (195, 232, 208);
 
+// Headless
+pub const HEADLESS_STATS_INTERVAL_SECS: f32 = 10.0;
+
+// Simulation
+pub const SIM_TICK_SECS: f32 = 1.0 / 60.0;
+pub const SIM_SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
 // Environment
 pub const W: usize = 10000;
 pub const H: usize = 10000;
+/// Only used by the soft boundary, cells this close to an edge start being pushed back
+pub const BOUNDARY_SOFT_MARGIN: f32 = 200.0;
+/// Velocity gained per second by a cell one margin past the inner edge
+pub const BOUNDARY_SOFT_PUSH: f32 = 600.0;
+pub const BOUNDARY_WALL_THICKNESS: f32 = 50.0;
+/// Rays ending this close to an edge count as crossing it, absorbs the float error
+pub const BOUNDARY_EDGE_TOLERANCE: f32 = 0.01;
 
 // GUI
 pub const MAX_GRAPH_POINTS: usize = 1500;
 pub const NN_NODE_SIZE: f32 = 10.0;
+pub const NN_VIZ_HEIGHT: f32 = 450.0;
+pub const NUM_TRAIT_HISTOGRAM_BINS: usize = 30;
 
 // Cell
 pub const NUM_CELLS: usize = 4000;
 pub const CELL_SPEED: f32 = 1.0;
+/// Collider radius
+pub const CELL_SIZE: f32 = 7.0;
+pub const CELL_SPRITE_SCALE: f32 = 1.5;
 pub const BASE_ENERGY: f32 = 100.0;
 pub const ENERGY_UPDATE_INTERVAL_SECS: f32 = 1.0;
 pub const ENERGY_DECAY_RATE: f32 = 5.0;
+/// Energy a cell pays for every child
+pub const REPRODUCTION_COST: f32 = 0.0;
 pub const UPDATE_INTERVAL: f32 = 0.5;
 pub const VISION_RADIUS: f32 = 200.0;
 pub const MAX_ENERGY: f32 = 4000.0;
@@ -25,36 +49,115 @@ pub const CELL_SPRITE: &str = "turret.png";
 pub const FOCUSED_CELL_SPRITE: &str = "turret-focused.png";
 pub const USER_CELL_SPRITE: &str = "turret-focused.png";
 
+// Body traits
+/// Chance of each body trait of a child changing, 0 keeps every cell on the defaults
+pub const TRAIT_MUTATION_RATE: f32 = 0.1;
+/// Largest relative change of a mutated trait
+pub const TRAIT_MUTATION_VARIATION: f32 = 0.1;
+/// Energy decay added per trait at twice its default, scaled like the fitness
+pub const TRAIT_ENERGY_COST: f32 = 1.0;
+/// Traits stay within these multiples of their defaults
+pub const TRAIT_MIN_SCALE: f32 = 0.25;
+pub const TRAIT_MAX_SCALE: f32 = 4.0;
+
 // Bullet
 pub const BULLET_LIFESPAN: f32 = 1.0;
 pub const BULLET_SPEED: f32 = 200.0;
 pub const BULLET_FIRE_RATE: f32 = 1.0;
 pub const BULLET_MISS_PENALTY: f32 = 5.0;
 pub const NO_BULLET_PENALTY: f32 = 30.0;
+
+// Kill rules
+pub const KILL_RULES_INTERVAL_SECS: f32 = 0.5;
+pub const IDLE_RULE_FROM_SECS: f32 = 10.0;
+pub const IDLE_RULE_UNTIL_SECS: f32 = 20.0;
+pub const IDLE_RULE_MIN_DISTANCE: f32 = 7.07;
+pub const REVOLVING_RULE_FROM_SECS: f32 = 15.0;
+pub const REVOLVING_RULE_UNTIL_SECS: f32 = 18.0;
+pub const REVOLVING_RULE_MIN_DISTANCE: f32 = 158.1;
+pub const ONE_DIRECTION_RULE_FROM_SECS: f32 = 20.0;
+pub const ONE_DIRECTION_RULE_UNTIL_SECS: f32 = 30.0;
+pub const ONE_DIRECTION_RULE_MAX_AXIS_RATIO: f32 = 3.0;
+pub const NO_BULLETS_RULE_AFTER_SECS: f32 = 8.0;
 pub const BULLET_SPRITE: &str = "brown-ball.png";
 
+// Combat
+pub const IS_COMBAT_ENABLED: bool = false;
+pub const BULLET_DAMAGE: f32 = 40.0;
+pub const KILL_BOUNTY: f32 = 150.0;
+pub const IS_FRIENDLY_FIRE_ENABLED: bool = false;
+
 // Food
 pub const NUM_FOOD: usize = 5000;
 pub const ENERGY_PER_FOOD: f32 = 70.0;
 pub const FOOD_REFRESH_INTERVAL_SECS: f32 = 0.5;
-pub const FOOD_TREE_REFRESH_RATE_SECS: f32 = 1.0;
 pub const FOOD_SPRITE: &str = "red-dot.png";
 
+// Sensors
+pub const IS_CELL_SENSOR_ENABLED: bool = true;
+pub const IS_BULLET_SENSOR_ENABLED: bool = true;
+pub const IS_ENERGY_SENSOR_ENABLED: bool = true;
+pub const IS_WALL_SENSOR_ENABLED: bool = false;
+/// Side of the buckets of the food, cell and bullet spatial grids
+pub const SPATIAL_GRID_CELL_SIZE: f32 = 100.0;
+pub const IS_VISION_ENABLED: bool = false;
+pub const NUM_VISION_RAYS: usize = 5;
+pub const VISION_FOV_DEGREES: f32 = 90.0;
+/// Edges a vision ray can cross when wrapping, rays are usually much shorter than the arena
+pub const VISION_MAX_RAY_LEGS: usize = 8;
+
 // NN
-pub const NUM_INPUT_NODES: usize = 3;
+/// Food distance, food angle and heading are always fed to the brain,
+/// the rest depends on the enabled sensors
+pub const NUM_BASE_INPUTS: usize = 3;
 pub const NUM_HIDDEN_NODES: usize = 8;
 pub const NUM_OUTPUT_NODES: usize = 4;
-pub const NET_ARCH: [usize; 3] = [NUM_INPUT_NODES, NUM_HIDDEN_NODES, NUM_OUTPUT_NODES];
+/// Extra outputs whose values are fed back as inputs on the next update
+pub const NUM_MEMORY_NEURONS: usize = 0;
+/// Picked from the `FitnessRegistry` by name
+pub const DEFAULT_FITNESS: &str = "Shaped";
+/// Score of every update with the survival fitness, halfway through the shaped range
+pub const SURVIVAL_FITNESS: f32 = 2.5;
 pub const BRAIN_MUTATION_RATE: f32 = 0.1;
 pub const BRAIN_MUTATION_VARIATION: f32 = 0.1;
+/// Chance of a child's dense brain switching one layer to another activation
+pub const ACTIVATION_MUTATION_RATE: f32 = 0.0;
+/// Children mix the brains of two parents instead of cloning one
+pub const IS_CROSSOVER_ENABLED: bool = false;
+pub const NEAT_ADD_NODE_RATE: f32 = 0.03;
+pub const NEAT_ADD_CONNECTION_RATE: f32 = 0.05;
+/// Genomes further apart than this belong to different species
+pub const NEAT_COMPATIBILITY_THRESHOLD: f64 = 3.0;
+pub const NEAT_EXCESS_COEFF: f64 = 1.0;
+pub const NEAT_DISJOINT_COEFF: f64 = 1.0;
+pub const NEAT_WEIGHT_COEFF: f64 = 0.4;
+pub const BRAINS_DIR: &str = "brains";
+
+// Snapshots
+pub const SNAPSHOT_DIR: &str = "snapshots";
+pub const QUICKSAVE_FILE: &str = "quicksave.json";
+pub const AUTOSAVE_INTERVAL_MINS: f32 = 10.0;
+pub const NUM_AUTOSAVE_SLOTS: usize = 3;
+
+// Stats recording
+pub const STATS_RECORD_INTERVAL_SECS: f32 = 5.0;
+
+// Genealogy
+pub const GENEALOGY_DIR: &str = "genealogy";
 
-/// Collision groups
-/// bit 1 - Cells
+/// Collision groups, counting bits from 0
+/// bit 1 - Bullet
 /// bit 2 - Food
-/// bit 3 - Bullet
+/// bit 3 - Cells
+/// bit 4 - Wall
+/// The combat masks are used instead while combat is enabled
+pub const GRP_WALL: u32 = 0b10000;
 pub const GRP_CELLS: u32 = 0b1000;
 pub const GRP_FOOD: u32 = 0b0100;
 pub const GRP_BULLET: u32 = 0b0010;
-pub const MASK_CELLS: u32 = 0b0000;
+pub const MASK_CELLS: u32 = 0b10000;
 pub const MASK_FOOD: u32 = 0b0010;
-pub const MASK_BULLET: u32 = 0b0100;
+pub const MASK_BULLET: u32 = 0b10100;
+pub const MASK_WALL: u32 = 0b1010;
+pub const MASK_CELLS_COMBAT: u32 = 0b10010;
+pub const MASK_BULLET_COMBAT: u32 = 0b11100;
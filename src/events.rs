@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+
+use crate::stats::DeathCause;
+
+/// Sent for every new cell, founders included
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CellBorn {
+    pub id: u32,
+    /// Id of the cell that replicated, None for founders
+    pub parent: Option<u32>,
+    /// Founding ancestor, the founder's own id for founders
+    pub lineage: u32,
+    pub pos: Vec2,
+}
+
+/// Sent for every cell that dies, whatever the cause
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CellDied {
+    pub id: u32,
+    pub cause: DeathCause,
+    /// Seconds
+    pub age: f32,
+    /// Energy left, zero or below unless a kill rule culled it
+    pub energy: f32,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BulletFired {
+    /// Id of the cell that shot, 0 for the user controlled cell
+    pub owner: u32,
+    pub pos: Vec2,
+    pub velocity: Vec2,
+}
+
+/// Sent for bullets that outlived `bullet_lifespan` without hitting anything
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BulletExpired {
+    pub owner: u32,
+    pub pos: Vec2,
+    /// Miss penalty taken from the owner, zero or below
+    pub energy_delta: f32,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct FoodEaten {
+    /// Id of the cell whose bullet hit the food
+    pub cell: u32,
+    pub pos: Vec2,
+    /// Energy the cell actually gained, less than `energy_per_food` near `max_energy`
+    pub energy_delta: f32,
+}
@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+
+use crate::{settings::DynamicSettings, *};
+
+/// Registry of fitness functions, the one named by `DynamicSettings::fitness` scores the cells
+pub struct FitnessPlugin;
+
+/// Scores a single brain update. Despite the name, higher is worse: the mean of
+/// the last scores scales the energy decay of the cell, 0 means no decay at all.
+/// The built-in ones stay within `1..=4`
+pub trait Fitness: Send + Sync + 'static {
+    /// Shown in the Settings panel and matched against `fitness` in the config
+    fn name(&self) -> &str;
+
+    /// `inputs` start with the food distance, food angle and heading, followed by
+    /// the enabled sensors. `outputs` are the action outputs: spin left, spin right,
+    /// thrust and shoot
+    fn score(&self, inputs: &[f64], outputs: &[f64; NUM_OUTPUT_NODES]) -> f32;
+}
+
+#[derive(Resource)]
+pub struct FitnessRegistry(Vec<Box<dyn Fitness>>);
+
+/// Lets library users plug in their own fitness functions
+pub trait RegisterFitness {
+    /// Replaces any fitness function with the same name
+    fn register_fitness(&mut self, fitness: impl Fitness) -> &mut Self;
+}
+
+/// The original hand-tuned rules: thrust when the food is far, turn towards it
+/// and shoot when it is close
+pub struct ShapedFitness;
+/// Only cares about getting to the food, shooting is left alone
+pub struct ForageFitness;
+/// Only cares about turning towards the food and shooting it when close
+pub struct AimFitness;
+/// No shaping, every cell decays at the same rate and only staying fed matters
+pub struct SurvivalFitness;
+
+impl Plugin for FitnessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FitnessRegistry>()
+            .add_systems(Startup, check_fitness_name);
+    }
+}
+
+impl RegisterFitness for App {
+    fn register_fitness(&mut self, fitness: impl Fitness) -> &mut Self {
+        self.init_resource::<FitnessRegistry>();
+        self.world
+            .resource_mut::<FitnessRegistry>()
+            .register(fitness);
+        self
+    }
+}
+
+fn check_fitness_name(registry: Res<FitnessRegistry>, settings: Res<DynamicSettings>) {
+    if registry.find(&settings.fitness).is_none() {
+        warn!(
+            "Unknown fitness {}, using {} instead. Registered: {}",
+            settings.fitness,
+            registry.default_fitness().name(),
+            registry.names().join(", ")
+        );
+    }
+}
+
+impl FitnessRegistry {
+    pub fn register(&mut self, fitness: impl Fitness) {
+        self.0.retain(|f| f.name() != fitness.name());
+        self.0.push(Box::new(fitness));
+    }
+
+    pub fn find(&self, name: &str) -> Option<&dyn Fitness> {
+        self.0.iter().find(|f| f.name() == name).map(|f| f.as_ref())
+    }
+
+    /// Falls back to the shaped rules for unknown names
+    pub fn get(&self, name: &str) -> &dyn Fitness {
+        self.find(name).unwrap_or_else(|| self.default_fitness())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.0.iter().map(|f| f.name()).collect()
+    }
+
+    fn default_fitness(&self) -> &dyn Fitness {
+        self.find(DEFAULT_FITNESS).unwrap_or(&ShapedFitness)
+    }
+}
+
+impl Default for FitnessRegistry {
+    fn default() -> Self {
+        let mut registry = Self(Vec::new());
+        registry.register(ShapedFitness);
+        registry.register(ForageFitness);
+        registry.register(AimFitness);
+        registry.register(SurvivalFitness);
+        registry
+    }
+}
+
+/// Actions picked from the outputs, the same way `act_cells_system` does
+struct Actions {
+    spin_left: bool,
+    spin_right: bool,
+    thrust: bool,
+    shoot: bool,
+}
+
+impl Actions {
+    fn new(out: &[f64; NUM_OUTPUT_NODES]) -> Self {
+        Self {
+            spin_left: out[0] > out[1],
+            spin_right: out[1] > out[0],
+            thrust: out[2] >= 0.7,
+            shoot: out[3] >= 0.7,
+        }
+    }
+
+    fn is_turning_to_target(&self, inp: &[f64]) -> bool {
+        let (target_angle, cell_angle) = (inp[1], inp[2]);
+        (cell_angle < target_angle && self.spin_left)
+            || (cell_angle > target_angle && self.spin_right)
+    }
+}
+
+/// Spreads the rules that weren't followed over `1..=4`, the range of the shaped rules
+fn missed_rules(rules: &[bool]) -> f32 {
+    let missed = rules.iter().filter(|followed| !**followed).count();
+    1.0 + 3.0 * missed as f32 / rules.len() as f32
+}
+
+impl Fitness for ShapedFitness {
+    fn name(&self) -> &str {
+        "Shaped"
+    }
+
+    fn score(&self, inp: &[f64], out: &[f64; NUM_OUTPUT_NODES]) -> f32 {
+        let dist = inp[0];
+        let actions = Actions::new(out);
+        let rules = [
+            dist > 0.3 && actions.thrust,
+            actions.is_turning_to_target(inp),
+            dist < 0.5 && actions.shoot,
+        ];
+        4.0 - rules.iter().filter(|followed| **followed).count() as f32
+    }
+}
+
+impl Fitness for ForageFitness {
+    fn name(&self) -> &str {
+        "Forage"
+    }
+
+    fn score(&self, inp: &[f64], out: &[f64; NUM_OUTPUT_NODES]) -> f32 {
+        let actions = Actions::new(out);
+        missed_rules(&[
+            inp[0] > 0.3 && actions.thrust,
+            actions.is_turning_to_target(inp),
+        ])
+    }
+}
+
+impl Fitness for AimFitness {
+    fn name(&self) -> &str {
+        "Aim"
+    }
+
+    fn score(&self, inp: &[f64], out: &[f64; NUM_OUTPUT_NODES]) -> f32 {
+        let actions = Actions::new(out);
+        missed_rules(&[
+            actions.is_turning_to_target(inp),
+            inp[0] < 0.5 && actions.shoot,
+        ])
+    }
+}
+
+impl Fitness for SurvivalFitness {
+    fn name(&self) -> &str {
+        "Survival"
+    }
+
+    fn score(&self, _: &[f64], _: &[f64; NUM_OUTPUT_NODES]) -> f32 {
+        SURVIVAL_FITNESS
+    }
+}
@@ -1,11 +1,15 @@
-use std::time::Duration;
-
-use bevy::{prelude::*, time::common_conditions::on_timer};
+use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
-use kd_tree::KdTree;
 use rand::Rng;
 
-use crate::{settings::DynamicSettings, *};
+use crate::{
+    rng::SimRng,
+    settings::DynamicSettings,
+    sim_config::SimConfig,
+    spatial::SpatialGrid,
+    trackers::{on_sim_timer, SimSet},
+    *,
+};
 
 pub struct FoodPlugin;
 
@@ -13,10 +17,10 @@ pub struct FoodPlugin;
 pub struct Food;
 
 #[derive(Resource)]
-pub struct FoodTree(pub Option<KdTree<[f32; 2]>>);
+pub struct FoodGrid(pub SpatialGrid<()>);
 
 #[derive(Bundle)]
-struct FoodBundle {
+pub struct FoodBundle {
     sprite_bundle: SpriteBundle,
     food: Food,
     rigid_body: RigidBody,
@@ -27,42 +31,39 @@ struct FoodBundle {
 
 impl Plugin for FoodPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(FoodTree(None))
+        app.insert_resource(FoodGrid(SpatialGrid::new(SPATIAL_GRID_CELL_SIZE)))
             .add_systems(Startup, setup)
             .add_systems(
-                Update,
-                spawn_food.run_if(on_timer(Duration::from_secs_f32(
-                    FOOD_REFRESH_INTERVAL_SECS,
-                ))),
+                FixedUpdate,
+                spawn_food
+                    .run_if(on_sim_timer(FOOD_REFRESH_INTERVAL_SECS))
+                    .in_set(SimSet::Logic),
             )
-            .add_systems(
-                Update,
-                reload_food_kd_tree.run_if(on_timer(Duration::from_secs_f32(
-                    FOOD_TREE_REFRESH_RATE_SECS,
-                ))),
-            );
+            .add_systems(FixedUpdate, update_food_grid.in_set(SimSet::Index));
     }
 }
 
 fn setup(
     commands: Commands,
-    asset_server: Res<AssetServer>,
+    config: Res<SimConfig>,
+    rng: ResMut<SimRng>,
     food_query: Query<With<Food>>,
     settings: Res<DynamicSettings>,
 ) {
-    spawn_food(commands, asset_server, settings, food_query);
+    spawn_food(commands, config, rng, settings, food_query);
 }
 
 fn spawn_food(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    config: Res<SimConfig>,
+    mut rng: ResMut<SimRng>,
     settings: Res<DynamicSettings>,
     food_query: Query<With<Food>>,
 ) {
-    let mut rng = rand::thread_rng();
+    let rng = &mut rng.0;
     let num_food = food_query.iter().len();
     let food_diff = 500;
-    if num_food > (settings.num_food - food_diff) {
+    if num_food > settings.num_food.saturating_sub(food_diff) {
         return;
     }
     let num_instances = if num_food == 0 {
@@ -77,28 +78,36 @@ fn spawn_food(
         2.5
     };
 
+    let (w, h) = (config.world_width as f32, config.world_height as f32);
     for _ in 0..num_instances {
-        let x = rng.gen_range(-(W as f32) / range_factor..W as f32 / range_factor);
-        let y = rng.gen_range(-(H as f32) / range_factor..H as f32 / range_factor);
-        commands.spawn(FoodBundle::new(x, y, &asset_server));
+        let x = rng.gen_range(-w / range_factor..w / range_factor);
+        let y = rng.gen_range(-h / range_factor..h / range_factor);
+        commands.spawn(FoodBundle::new(x, y));
     }
 }
 
-fn reload_food_kd_tree(food_query: Query<&Transform, With<Food>>, mut food_tree: ResMut<FoodTree>) {
-    let mut pts = Vec::new();
-    for t in food_query.iter() {
-        pts.push([t.translation.x, t.translation.y])
+fn update_food_grid(
+    mut food_grid: ResMut<FoodGrid>,
+    mut removed: RemovedComponents<Food>,
+    moved_query: Query<(Entity, &Transform), (With<Food>, Changed<Transform>)>,
+    food_query: Query<(), With<Food>>,
+) {
+    for entity in removed.iter() {
+        food_grid.0.remove(entity);
     }
-
-    food_tree.0 = Some(KdTree::build_by_ordered_float(pts));
+    for (entity, t) in moved_query.iter() {
+        food_grid.0.insert(entity, t.translation.truncate(), ());
+    }
+    food_grid
+        .0
+        .prune(food_query.iter().len(), |e| food_query.contains(e));
 }
 
 impl FoodBundle {
-    fn new(x: f32, y: f32, asset_server: &AssetServer) -> Self {
+    pub fn new(x: f32, y: f32) -> Self {
         Self {
             sprite_bundle: SpriteBundle {
                 transform: Transform::from_xyz(x, y, 0.0).with_scale(Vec3::splat(2.0)),
-                texture: asset_server.load(FOOD_SPRITE),
                 ..default()
             },
             food: Food,
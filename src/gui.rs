@@ -1,27 +1,33 @@
-use std::time::Duration;
-
-use bevy::{math::vec3, prelude::*, time::common_conditions::on_timer};
+use bevy::{ecs::system::SystemParam, prelude::*};
 use bevy_egui::{
     egui::{
         self,
         epaint::CircleShape,
-        plot::{Line, Plot, PlotPoints},
+        plot::{Bar, BarChart, Line, Plot, PlotPoints},
         pos2, Color32, Shape, Stroke,
     },
     EguiContexts, EguiPlugin, EguiSettings,
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    brain::{BrainNet, NodeKind},
     bullet::Bullet,
     camera::FollowCamera,
     cell::{
-        energy::EnergyMap,
+        energy::EnergyReason,
         focus::{FocusedCell, FocusedCellNet, FocusedCellStats, UnFocusCellEvent},
-        Cell,
+        genealogy::Genealogy,
+        traits::{BodyTrait, BodyTraits},
+        Brain, Cell, CellGrid,
     },
-    food::{Food, FoodTree},
+    fitness::FitnessRegistry,
+    food::{Food, FoodGrid},
+    nn::Activation,
     settings::{DynamicSettings, SimSettings},
-    trackers::{BirthTs, InstantTracker},
+    sim_config::SimConfig,
+    stats::{DeathCause, Demographics, SimStats},
+    trackers::{on_sim_timer, SimClock, SimSet, TimeControl},
     *,
 };
 
@@ -32,39 +38,59 @@ enum Panel {
     Stats,
     Graphs,
     Network,
+    Lineage,
     Settings,
 }
 #[derive(Resource)]
 struct SelectedPanel(Panel);
 
-#[derive(Resource)]
-pub struct SimStats {
-    pub max_score: f32,
-    pub max_age: f32,
-    pub best_cell_pos: Vec2,
-    pub oldest_cell_pos: Vec2,
-    pub sim_start_ts: InstantTracker,
+/// Settings shown and edited from the side panel
+#[derive(SystemParam)]
+struct PanelSettings<'w> {
+    sim: ResMut<'w, SimSettings>,
+    dynamic: ResMut<'w, DynamicSettings>,
+    time_control: ResMut<'w, TimeControl>,
+    config: Res<'w, SimConfig>,
+    fitness_registry: Res<'w, FitnessRegistry>,
 }
 
-#[derive(Resource, Default)]
-struct GraphPoints {
+/// Population stats shown in the stats panel
+#[derive(SystemParam)]
+struct PanelStats<'w> {
+    sim: Res<'w, SimStats>,
+    demographics: Res<'w, Demographics>,
+}
+
+/// Genealogy shown and exported from the lineage panel
+#[derive(SystemParam)]
+struct LineageView<'w, 's> {
+    genealogy: Res<'w, Genealogy>,
+    export_status: Local<'s, String>,
+}
+
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct GraphPoints {
     score: Vec<f32>,
     age: Vec<f32>,
     num_cells: Vec<f32>,
+    /// Number of cells per bin of each body trait, relative to the defaults.
+    /// Rebuilt from the living cells, so it isn't worth saving
+    #[serde(skip)]
+    traits: Vec<(BodyTrait, Vec<usize>)>,
 }
 
 impl Plugin for GuiPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(EguiPlugin)
-            .insert_resource(SimStats::new())
             .insert_resource(SelectedPanel(Panel::Stats))
             .insert_resource(GraphPoints::default())
             .add_systems(Startup, setup)
-            .add_systems(Update, update_stats)
             .add_systems(Update, handle_mouse_btn_click)
             .add_systems(
-                Update,
-                update_graph_points.run_if(on_timer(Duration::from_secs_f32(1.0))),
+                FixedUpdate,
+                update_graph_points
+                    .run_if(on_sim_timer(1.0))
+                    .in_set(SimSet::PostPhysics),
             )
             .add_systems(Update, update_side_panel);
     }
@@ -77,27 +103,25 @@ fn setup(mut egui_settings: ResMut<EguiSettings>) {
 fn update_side_panel(
     mut contexts: EguiContexts,
     mut panel: ResMut<SelectedPanel>,
-    energy_map: Res<EnergyMap>,
-    stats: Res<SimStats>,
+    clock: Res<SimClock>,
+    stats: PanelStats,
     graph_points: Res<GraphPoints>,
     focused_cell_stats: Res<FocusedCellStats>,
-    mut settings: ResMut<SimSettings>,
-    mut dynamic_settings: ResMut<DynamicSettings>,
-    food_tree: Res<FoodTree>,
+    mut settings: PanelSettings,
+    food_grid: Res<FoodGrid>,
     best_brain: Res<FocusedCellNet>,
+    mut brain_save_status: Local<String>,
+    mut lineage: LineageView,
     cells_query: Query<(&Cell, &Transform), With<Cell>>,
+    focused_brain_query: Query<(&Cell, &Brain), With<FocusedCell>>,
     food_query: Query<With<Food>>,
     bullet_query: Query<With<Bullet>>,
 ) {
-    if !settings.show_side_panel {
+    if !settings.sim.show_side_panel {
         return;
     }
 
     let ctx = contexts.ctx_mut();
-    let tree_size = match &food_tree.0 {
-        Some(v) => v.len(),
-        None => 0,
-    };
 
     egui::SidePanel::left("left-side-panel")
         .min_width(200.0)
@@ -107,6 +131,7 @@ fn update_side_panel(
                 ui.selectable_value(&mut panel.0, Panel::Stats, Panel::Stats.get_label());
                 ui.selectable_value(&mut panel.0, Panel::Graphs, Panel::Graphs.get_label());
                 ui.selectable_value(&mut panel.0, Panel::Network, Panel::Network.get_label());
+                ui.selectable_value(&mut panel.0, Panel::Lineage, Panel::Lineage.get_label());
                 ui.selectable_value(&mut panel.0, Panel::Settings, Panel::Settings.get_label());
             });
             ui.separator();
@@ -119,8 +144,20 @@ fn update_side_panel(
                             ui.label(format!("Cells: {:?}", cells_query.iter().len()));
                             ui.label(format!("Food: {:?}", food_query.iter().len()));
                             ui.label(format!("Bullets: {:?}", bullet_query.iter().len()));
-                            ui.label(format!("Max Fitness: {:?}", stats.max_score));
-                            ui.label(format!("Max Lifespan: {:.2}", stats.max_age));
+                            ui.label(format!("Max Fitness: {:?}", stats.sim.max_score));
+                            ui.label(format!("Max Lifespan: {:.2}", stats.sim.max_age));
+                        });
+                    egui::CollapsingHeader::new("Deaths")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            ui.label(format!("Births: {}", stats.demographics.births));
+                            for cause in DeathCause::ALL {
+                                ui.label(format!(
+                                    "{}: {}",
+                                    cause.label(),
+                                    stats.demographics.deaths(cause)
+                                ));
+                            }
                         });
                     egui::CollapsingHeader::new("Cell")
                         .default_open(true)
@@ -144,17 +181,34 @@ fn update_side_panel(
                                     "Fitness: {:?}",
                                     focused_cell_stats.fitness_score
                                 ));
+                                if let Some(traits) = focused_cell_stats.traits {
+                                    ui.label(format!(
+                                        "Speed: {:.2}, Vision: {:.0}",
+                                        traits.speed, traits.vision_radius
+                                    ));
+                                    ui.label(format!(
+                                        "Fire Rate: {:.2} s, Size: {:.1}",
+                                        traits.fire_rate, traits.size
+                                    ));
+                                }
+                                if let Some(ledger) = focused_cell_stats.ledger {
+                                    ui.label("Energy (in / out):");
+                                    for reason in EnergyReason::ALL {
+                                        ui.label(format!(
+                                            "  {}: {:.0} / {:.0}",
+                                            reason.label(),
+                                            ledger.income(reason),
+                                            ledger.expense(reason)
+                                        ));
+                                    }
+                                }
                             }
                         });
                     egui::CollapsingHeader::new("Debug")
                         .default_open(false)
                         .show(ui, |ui| {
-                            ui.label(format!("Energy Map: {:?}", energy_map.0.len()));
-                            ui.label(format!("Food Tree: {:?}", tree_size));
-                            ui.label(format!(
-                                "Runtime: {:.1} m",
-                                stats.sim_start_ts.elapsed() / 60.0
-                            ));
+                            ui.label(format!("Food Grid: {:?}", food_grid.0.len()));
+                            ui.label(format!("Runtime: {:.1} m", clock.elapsed() / 60.0));
                         });
                 }
                 Panel::Graphs => {
@@ -195,62 +249,238 @@ fn update_side_panel(
                                 .view_aspect(aspect)
                                 .show(ui, |plot_ui| plot_ui.line(line3));
                         });
+                    egui::CollapsingHeader::new("Body Traits (x default)")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            for (t, bins) in graph_points.traits.iter() {
+                                ui.label(t.label());
+                                Plot::new(t.label())
+                                    .view_aspect(aspect)
+                                    .show(ui, |plot_ui| {
+                                        plot_ui.bar_chart(get_trait_histogram(bins))
+                                    });
+                            }
+                        });
                 }
                 Panel::Network => {
-                    let shapes = get_nn_shapes(&best_brain);
+                    let shapes = get_nn_shapes(&best_brain, ui.available_width());
                     if shapes.is_empty() {
                         ui.label("Select a cell first");
                     }
                     shapes.iter().for_each(|s| {
                         ui.painter().add(s.clone());
                     });
+
+                    if let Some((cell, brain)) = focused_brain_query.iter().next() {
+                        ui.add_space(NN_VIZ_HEIGHT + NN_NODE_SIZE * 2.0);
+                        if let BrainNet::Dense(net) = &brain.0 {
+                            ui.label(format!("Activations: {:?}", net.activations()));
+                        }
+                        if ui.button("Save brain").clicked() {
+                            let path = format!("{}/cell-{}.json", BRAINS_DIR, cell.0);
+                            *brain_save_status = match brain.0.save(&path) {
+                                Ok(_) => format!("Saved to {}", path),
+                                Err(e) => format!("Save failed: {}", e),
+                            };
+                        }
+                        ui.label(brain_save_status.as_str());
+                    }
+                }
+                Panel::Lineage => {
+                    egui::CollapsingHeader::new("Ancestors")
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            if !focused_cell_stats.is_cell_focused() {
+                                ui.label("Select a cell first");
+                                return;
+                            }
+                            let chain = lineage.genealogy.ancestry(focused_cell_stats.id);
+                            if let Some((_, record)) = chain.first() {
+                                ui.label(format!("Generation: {}", record.generation));
+                            }
+                            egui::ScrollArea::vertical()
+                                .max_height(NN_VIZ_HEIGHT)
+                                .show(ui, |ui| {
+                                    for (id, record) in chain.iter() {
+                                        let death = match record.death {
+                                            Some(t) => format!("died {:.1}s", t.secs()),
+                                            None => "alive".to_string(),
+                                        };
+                                        ui.label(format!(
+                                            "#{} gen {}, born {:.1}s, {}",
+                                            id,
+                                            record.generation,
+                                            record.birth.secs(),
+                                            death
+                                        ));
+                                    }
+                                });
+                        });
+                    egui::CollapsingHeader::new("Export")
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            ui.label(format!("Recorded cells: {}", lineage.genealogy.len()));
+                            let base = format!("{}/genealogy-{}", GENEALOGY_DIR, clock.ticks());
+                            ui.horizontal(|ui| {
+                                if ui.button("Export DOT").clicked() {
+                                    let path = format!("{}.dot", base);
+                                    *lineage.export_status =
+                                        export_status(&path, lineage.genealogy.save_dot(&path));
+                                }
+                                if ui.button("Export Newick").clicked() {
+                                    let path = format!("{}.nwk", base);
+                                    *lineage.export_status =
+                                        export_status(&path, lineage.genealogy.save_newick(&path));
+                                }
+                            });
+                            ui.label(lineage.export_status.as_str());
+                        });
                 }
                 Panel::Settings => {
                     egui::CollapsingHeader::new("Camera")
                         .default_open(true)
                         .show(ui, |ui| {
-                            ui.checkbox(&mut settings.follow_best, "Follow Best");
-                            ui.checkbox(&mut settings.follow_oldest, "Follow Oldest");
-                            ui.checkbox(&mut settings.follow_player, "Follow Player");
-                            ui.checkbox(&mut settings.follow_focused_cell, "Follow Focused Cell");
+                            ui.checkbox(&mut settings.sim.follow_best, "Follow Best");
+                            ui.checkbox(&mut settings.sim.follow_oldest, "Follow Oldest");
+                            ui.checkbox(&mut settings.sim.follow_player, "Follow Player");
+                            ui.checkbox(
+                                &mut settings.sim.follow_focused_cell,
+                                "Follow Focused Cell",
+                            );
+                        });
+                    egui::CollapsingHeader::new("Time")
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            ui.checkbox(&mut settings.time_control.paused, "Paused");
+                            ui.horizontal(|ui| {
+                                let step = ui.add_enabled(
+                                    settings.time_control.paused,
+                                    egui::Button::new("Step"),
+                                );
+                                if step.clicked() {
+                                    settings.time_control.step();
+                                }
+                                if ui.button("Slower").clicked() {
+                                    settings.time_control.slow_down();
+                                }
+                                if ui.button("Faster").clicked() {
+                                    settings.time_control.speed_up();
+                                }
+                            });
+                            ui.label(format!("Speed: {}x", settings.time_control.speed));
+                        });
+                    egui::CollapsingHeader::new("Fitness")
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            let fitness = &mut settings.dynamic.fitness;
+                            egui::ComboBox::from_id_source("fitness")
+                                .selected_text(fitness.as_str())
+                                .show_ui(ui, |ui| {
+                                    for name in settings.fitness_registry.names() {
+                                        ui.selectable_value(fitness, name.to_string(), name);
+                                    }
+                                });
+                        });
+                    egui::CollapsingHeader::new("Combat")
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            ui.checkbox(&mut settings.dynamic.is_combat_enabled, "Enabled");
+                            ui.checkbox(
+                                &mut settings.dynamic.is_friendly_fire_enabled,
+                                "Friendly fire",
+                            );
+                            ui.label("Bullet damage");
+                            ui.add(
+                                egui::DragValue::new(&mut settings.dynamic.bullet_damage)
+                                    .speed(1.0)
+                                    .clamp_range(0.0..=1000.0),
+                            );
+                            ui.label("Kill bounty");
+                            ui.add(
+                                egui::DragValue::new(&mut settings.dynamic.kill_bounty)
+                                    .speed(1.0)
+                                    .clamp_range(0.0..=settings.config.max_energy),
+                            );
+                        });
+                    egui::CollapsingHeader::new("Kill Rules")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            let rules = &mut settings.dynamic.kill_rules;
+                            ui.checkbox(&mut rules.idle.is_enabled, "Idle");
+                            ui.checkbox(&mut rules.revolving.is_enabled, "Revolving");
+                            ui.checkbox(&mut rules.one_direction.is_enabled, "One direction");
+                            ui.checkbox(&mut rules.no_bullets.is_enabled, "No bullets");
+                            ui.label("Idle min distance");
+                            ui.add(
+                                egui::DragValue::new(&mut rules.idle.min_distance)
+                                    .speed(1.0)
+                                    .clamp_range(0.0..=1000.0),
+                            );
+                            ui.label("Revolving min distance");
+                            ui.add(
+                                egui::DragValue::new(&mut rules.revolving.min_distance)
+                                    .speed(1.0)
+                                    .clamp_range(0.0..=1000.0),
+                            );
+                            ui.label("One direction max axis ratio");
+                            ui.add(
+                                egui::DragValue::new(&mut rules.one_direction.max_axis_ratio)
+                                    .speed(0.1)
+                                    .clamp_range(1.0..=100.0),
+                            );
+                            ui.label("No bullets penalty");
+                            ui.add(
+                                egui::DragValue::new(&mut rules.no_bullets.penalty)
+                                    .speed(1.0)
+                                    .clamp_range(0.0..=300.0),
+                            );
                         });
                     egui::CollapsingHeader::new("Others")
                         .default_open(true)
                         .show(ui, |ui| {
                             ui.label("Bullet miss penalty");
                             ui.add(
-                                egui::DragValue::new(&mut dynamic_settings.bullet_miss_penalty)
+                                egui::DragValue::new(&mut settings.dynamic.bullet_miss_penalty)
                                     .speed(1.0)
                                     .clamp_range(0.0..=300.0),
                             );
                             ui.label("Energy per food");
                             ui.add(
-                                egui::DragValue::new(&mut dynamic_settings.energy_per_food)
+                                egui::DragValue::new(&mut settings.dynamic.energy_per_food)
                                     .speed(1.0)
                                     .clamp_range(0.0..=1000.0),
                             );
                             ui.label("Energy decay rate");
                             ui.add(
-                                egui::DragValue::new(&mut dynamic_settings.energy_decay_rate)
+                                egui::DragValue::new(&mut settings.dynamic.energy_decay_rate)
                                     .speed(1.0)
                                     .clamp_range(0.0..=1000.0),
                             );
                             ui.label("Num food");
-                            ui.add(egui::DragValue::new(&mut dynamic_settings.num_food).speed(1.0));
+                            ui.add(egui::DragValue::new(&mut settings.dynamic.num_food).speed(1.0));
                         });
                 }
             }
         });
 }
 
+fn export_status(path: &str, result: std::io::Result<()>) -> String {
+    match result {
+        Ok(_) => format!("Saved to {}", path),
+        Err(e) => format!("Export failed: {}", e),
+    }
+}
+
 fn update_graph_points(
     stats: Res<SimStats>,
+    config: Res<SimConfig>,
     mut graph_points: ResMut<GraphPoints>,
-    cells_query: Query<With<Cell>>,
+    cells_query: Query<&BodyTraits, With<Cell>>,
 ) {
     graph_points.add_age(stats.max_age);
     graph_points.add_score(stats.max_score);
     graph_points.add_num_cells(cells_query.iter().count() as f32);
+    graph_points.set_traits(cells_query.iter(), &config);
 }
 
 fn handle_mouse_btn_click(
@@ -259,8 +489,8 @@ fn handle_mouse_btn_click(
     mut writer: EventWriter<UnFocusCellEvent>,
     mouse: Res<Input<MouseButton>>,
     cam_query: Query<(&Camera, &GlobalTransform), With<FollowCamera>>,
-    cells_query: Query<(&Transform, Entity), (With<Cell>, Without<FocusedCell>)>,
-    focused_cells_query: Query<(&Cell, &Transform), With<FocusedCell>>,
+    cell_grid: Res<CellGrid>,
+    focused_cells_query: Query<&Cell, With<FocusedCell>>,
 ) {
     if !mouse.just_pressed(MouseButton::Left) {
         return;
@@ -276,180 +506,139 @@ fn handle_mouse_btn_click(
         return;
     }
     let world_pos = world_pos.unwrap();
-    let (x, y) = (world_pos.x, world_pos.y);
 
-    for (t, e) in cells_query.iter() {
-        let dist = t.translation.distance_squared(vec3(x, y, 0.0));
-        if dist <= 100.0 {
-            // Remove all cells that are focused
-            for (c, _) in focused_cells_query.iter() {
-                writer.send(UnFocusCellEvent(c.0));
-            }
-            // When marking a cell as focused,
-            // We simply add the component to the entity so that the cell system can update their sprite
-            commands.entity(e).insert(FocusedCell);
-            return;
-        }
-    }
-    for (c, t) in focused_cells_query.iter() {
-        let dist = t.translation.distance_squared(vec3(x, y, 0.0));
-        if dist <= 100.0 {
+    let Some(clicked) = cell_grid.0.nearest(world_pos, 10.0, |_| true) else {
+        return;
+    };
+    match focused_cells_query.get(clicked.entity) {
+        Ok(c) => {
             // When un-focusing a cell, we create an event with index
             // In the cell system, we get all the focused cells, then loop over all focused cells
             // Then unfocus this cell by id (ie remove the focus component)
             // If the focus component is removed here, then we'll have to iterate over all cells to find the cell by id
             writer.send(UnFocusCellEvent(c.0));
-            return;
-        }
-    }
-}
-
-fn update_stats(
-    mut stats: ResMut<SimStats>,
-    energy_map: Res<EnergyMap>,
-    cells_query: Query<(&Cell, &BirthTs, &Transform), With<Cell>>,
-) {
-    let mut max_score = 0.0;
-    let mut max_age = 0.0;
-    let mut best_cell_pos = Vec3::ZERO;
-    let mut oldest_cell_pos = Vec3::ZERO;
-
-    for (c, birth_ts, transform) in cells_query.iter() {
-        let score = match energy_map.0.get(&c.0) {
-            Some((v, _)) => *v,
-            None => 0.0,
-        };
-        if score > max_score {
-            max_score = score;
-            best_cell_pos = transform.translation;
         }
-
-        let age = birth_ts.0.elapsed();
-        if age > max_age {
-            max_age = age;
-            oldest_cell_pos = transform.translation;
+        Err(_) => {
+            // Remove all cells that are focused
+            for c in focused_cells_query.iter() {
+                writer.send(UnFocusCellEvent(c.0));
+            }
+            // When marking a cell as focused,
+            // We simply add the component to the entity so that the cell system can update their sprite
+            commands.entity(clicked.entity).insert(FocusedCell);
         }
     }
+}
 
-    stats.max_score = max_score;
-    stats.max_age = max_age;
-    stats.best_cell_pos = best_cell_pos.truncate();
-    stats.oldest_cell_pos = oldest_cell_pos.truncate();
+/// Bars over `TRAIT_MIN_SCALE..TRAIT_MAX_SCALE`, the range traits are kept within
+fn get_trait_histogram(bins: &[usize]) -> BarChart {
+    let width = (TRAIT_MAX_SCALE - TRAIT_MIN_SCALE) as f64 / bins.len() as f64;
+    let bars = bins
+        .iter()
+        .enumerate()
+        .map(|(i, n)| {
+            let x = TRAIT_MIN_SCALE as f64 + width * (i as f64 + 0.5);
+            Bar::new(x, *n as f64).width(width)
+        })
+        .collect();
+    BarChart::new(bars)
 }
 
-fn get_nn_shapes(best_brain: &FocusedCellNet) -> Vec<Shape> {
-    if best_brain.0.is_empty() {
+fn get_nn_shapes(best_brain: &FocusedCellNet, width: f32) -> Vec<Shape> {
+    let graph = &best_brain.graph;
+    let values = &best_brain.values;
+    if graph.nodes.is_empty() || graph.nodes.len() != values.len() {
         return Vec::new();
     }
 
     let mut shapes = Vec::new();
-    let tot_height = 450.0;
+    let tot_height = NN_VIZ_HEIGHT;
 
     // Padding
     let padding_top = 30.0;
-    // NN viz points
-    let points1 = get_nn_viz_points(NUM_INPUT_NODES as usize, tot_height);
-    let points2 = get_nn_viz_points(NUM_HIDDEN_NODES as usize, tot_height);
-    let points3 = get_nn_viz_points(NUM_OUTPUT_NODES as usize, tot_height);
-    // NN output
-    let values1 = best_brain.0[0].clone();
-    let values2 = best_brain.0[1].clone();
-    let values3 = best_brain.0[2].clone();
-    // x's
-    let x1 = 25.0;
-    let x2 = 100.0;
-    let x3 = 175.0;
-
-    // colors
-    let colors1: Vec<Color32> = values1
+    // One column of nodes per depth, squeezed to fit deep NEAT graphs
+    let num_columns = graph.nodes.iter().map(|n| n.depth).max().unwrap_or(0) + 1;
+    let spacing = ((width - 50.0) / (num_columns - 1).max(1) as f32).min(75.0);
+    let mut columns = vec![Vec::new(); num_columns];
+    for (i, node) in graph.nodes.iter().enumerate() {
+        columns[node.depth].push(i);
+    }
+    let mut positions = vec![pos2(0.0, 0.0); graph.nodes.len()];
+    for (depth, column) in columns.iter().enumerate() {
+        let x = 25.0 + depth as f32 * spacing;
+        let points = get_nn_viz_points(column.len(), tot_height);
+        for (i, y) in column.iter().zip(points) {
+            positions[*i] = pos2(x, y + padding_top);
+        }
+    }
+
+    // Actions come first among the outputs, memory outputs are drawn like hidden nodes
+    let first_output = graph
+        .nodes
         .iter()
-        .rev()
-        .map(|v| {
-            if *v <= 0.7 {
-                Color32::GREEN
-            } else {
-                Color32::RED
-            }
-        })
-        .collect();
-    let colors2: Vec<Color32> = values2
+        .position(|n| n.kind == NodeKind::Output)
+        .unwrap_or(0);
+    let output_colors = get_output_colors(&values[first_output..first_output + NUM_OUTPUT_NODES]);
+    let colors: Vec<Color32> = graph
+        .nodes
         .iter()
-        .rev()
-        .map(|v| {
-            if *v > 0.5 {
-                Color32::GREEN
-            } else {
-                Color32::RED
-            }
+        .enumerate()
+        .map(|(i, node)| match node.kind {
+            NodeKind::Input => get_input_color(values[i]),
+            NodeKind::Hidden => get_hidden_color(values[i], node.activation),
+            NodeKind::Output => match output_colors.get(i - first_output) {
+                Some(color) => *color,
+                None => get_hidden_color(values[i], node.activation),
+            },
         })
         .collect();
-    let mut colors3 = vec![Color32::RED, Color32::RED, Color32::RED, Color32::RED];
-    colors3[0] = if values3[0] >= values3[1] {
-        Color32::GREEN
-    } else {
-        Color32::RED
-    };
-    colors3[1] = if values3[1] >= values3[0] {
-        Color32::GREEN
-    } else {
-        Color32::RED
-    };
-    colors3[2] = if values3[2] >= 0.7 {
-        Color32::GREEN
-    } else {
-        Color32::RED
-    };
-    colors3[3] = if values3[3] >= 0.7 {
-        Color32::GREEN
-    } else {
-        Color32::RED
-    };
 
-    // layer 1 -> 2 lines
-    for (p1, c1) in points1.iter().zip(colors1.iter()) {
-        for (p2, c2) in points2.iter().zip(colors2.iter()) {
-            let mut color = Color32::RED;
-            if are_colors_equal(*c1, *c2) {
-                color = Color32::GREEN;
-            }
-            shapes.push(egui::Shape::line(
-                vec![pos2(x1, *p1 + padding_top), pos2(x2, *p2 + padding_top)],
-                Stroke { width: 1.0, color },
-            ));
+    // connections
+    for edge in graph.edges.iter() {
+        let mut color = Color32::RED;
+        if are_colors_equal(colors[edge.from], colors[edge.to]) {
+            color = Color32::GREEN;
         }
+        shapes.push(egui::Shape::line(
+            vec![positions[edge.from], positions[edge.to]],
+            Stroke { width: 1.0, color },
+        ));
     }
 
-    // layer 2 -> 3 lines
-    for (p2, c2) in points2.iter().zip(colors2.iter()) {
-        for (p3, c3) in points3.iter().zip(colors3.iter()) {
-            let mut color = Color32::RED;
-            if are_colors_equal(*c2, *c3) {
-                color = Color32::GREEN;
-            }
-            shapes.push(egui::Shape::line(
-                vec![pos2(x2, *p2 + padding_top), pos2(x3, *p3 + padding_top)],
-                Stroke { width: 1.0, color },
-            ));
-        }
+    // nodes
+    for (p, c) in positions.iter().zip(colors.iter()) {
+        shapes.push(get_nn_node_shape(p.x, p.y, *c));
     }
 
-    // layer 1
-    let padding = padding_top;
-    for (p, c) in points1.iter().zip(colors1.iter()) {
-        shapes.push(get_nn_node_shape(x1, *p + padding, *c));
-    }
-    // layer 2
-    let padding = padding_top;
-    for (p, c) in points2.iter().zip(colors2.iter()) {
-        shapes.push(get_nn_node_shape(x2, *p + padding, *c));
+    shapes
+}
+
+fn get_input_color(value: f64) -> Color32 {
+    if value <= 0.7 {
+        Color32::GREEN
+    } else {
+        Color32::RED
     }
-    // layer 3
-    let padding = padding_top;
-    for (p, c) in points3.iter().zip(colors3.iter()) {
-        shapes.push(get_nn_node_shape(x3, *p + padding, *c));
+}
+
+/// Lit when the node is above the output of its activation for a zero input
+fn get_hidden_color(value: f64, activation: Activation) -> Color32 {
+    if value > activation.midpoint() {
+        Color32::GREEN
+    } else {
+        Color32::RED
     }
+}
 
-    shapes
+/// Spin nodes light up for the winning direction, thrust and shoot past their threshold
+fn get_output_colors(values: &[f64]) -> Vec<Color32> {
+    let on_off = |is_on: bool| if is_on { Color32::GREEN } else { Color32::RED };
+    vec![
+        on_off(values[0] >= values[1]),
+        on_off(values[1] >= values[0]),
+        on_off(values[2] >= 0.7),
+        on_off(values[3] >= 0.7),
+    ]
 }
 
 fn get_nn_node_shape(x: f32, y: f32, color: Color32) -> egui::Shape {
@@ -480,24 +669,13 @@ fn are_colors_equal(first: Color32, second: Color32) -> bool {
     (first.g() == 255 && second.g() == 255) || (first.r() == 255 && second.r() == 255)
 }
 
-impl SimStats {
-    fn new() -> Self {
-        Self {
-            best_cell_pos: Vec2::ZERO,
-            max_age: 0.0,
-            max_score: 0.0,
-            oldest_cell_pos: Vec2::ZERO,
-            sim_start_ts: InstantTracker::default(),
-        }
-    }
-}
-
 impl Panel {
     fn get_label(&self) -> &str {
         match self {
             Panel::Stats => "Stats",
             Panel::Graphs => "Graphs",
             Panel::Network => "Network",
+            Panel::Lineage => "Lineage",
             Panel::Settings => "Settings",
         }
     }
@@ -518,6 +696,27 @@ impl GraphPoints {
         }
     }
 
+    pub fn set_traits<'a>(
+        &mut self,
+        traits: impl Iterator<Item = &'a BodyTraits>,
+        config: &SimConfig,
+    ) {
+        let mut histograms: Vec<(BodyTrait, Vec<usize>)> = BodyTrait::ALL
+            .iter()
+            .map(|t| (*t, vec![0; NUM_TRAIT_HISTOGRAM_BINS]))
+            .collect();
+        let range = TRAIT_MAX_SCALE - TRAIT_MIN_SCALE;
+        for traits in traits {
+            for (t, bins) in histograms.iter_mut() {
+                let value = (traits.relative(*t, config) - TRAIT_MIN_SCALE) / range;
+                let i = ((value * NUM_TRAIT_HISTOGRAM_BINS as f32) as usize)
+                    .min(NUM_TRAIT_HISTOGRAM_BINS - 1);
+                bins[i] += 1;
+            }
+        }
+        self.traits = histograms;
+    }
+
     pub fn add_num_cells(&mut self, value: f32) {
         self.num_cells.push(value);
         if self.num_cells.len() > MAX_GRAPH_POINTS {
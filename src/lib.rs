@@ -1,12 +1,25 @@
+pub mod boundary;
+pub mod brain;
 pub mod bullet;
 pub mod camera;
 pub mod cell;
+pub mod cli;
 pub mod configs;
+pub mod events;
+pub mod fitness;
 pub mod food;
 pub mod gui;
+pub mod neat;
 pub mod nn;
 pub mod physics;
+pub mod recorder;
+pub mod rng;
 pub mod settings;
+pub mod sim_config;
+pub mod snapshot;
+pub mod spatial;
+pub mod stats;
 pub mod trackers;
+pub mod visuals;
 
 pub use configs::*;
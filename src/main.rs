@@ -1,6 +1,9 @@
 use ava::{
-    bullet::BulletPlugin, camera::FollowCameraPlugin, cell::CellPlugin, food::FoodPlugin,
-    gui::GuiPlugin, physics::PhysicsPlugin, settings::SettingsPlugin, trackers::TrackersPlugin, *,
+    bullet::BulletPlugin, camera::FollowCameraPlugin, cell::user::UserCellPlugin, cell::CellPlugin,
+    cli::CliArgs, food::FoodPlugin, gui::GuiPlugin, physics::PhysicsPlugin,
+    recorder::StatsRecorderPlugin, rng::RngPlugin, settings::SettingsPlugin,
+    snapshot::SnapshotPlugin, stats::StatsPlugin, trackers::TrackersPlugin, visuals::VisualsPlugin,
+    *,
 };
 use bevy::{
     diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
@@ -11,6 +14,8 @@ use bevy::{
 struct UICameraFollower;
 
 fn main() {
+    let args = CliArgs::parse();
+
     App::new()
         .add_plugins(
             DefaultPlugins
@@ -32,13 +37,24 @@ fn main() {
         .insert_resource(ClearColor(Color::rgba_u8(
             BG_COLOR.0, BG_COLOR.1, BG_COLOR.2, 0,
         )))
+        .insert_resource(args.config.clone())
         .add_plugins(GuiPlugin)
         .add_plugins(SettingsPlugin)
         .add_plugins(TrackersPlugin)
+        .add_plugins(RngPlugin {
+            seed: args.config.seed,
+        })
         .add_plugins(PhysicsPlugin)
         .add_plugins(FollowCameraPlugin)
         .add_plugins(BulletPlugin)
         .add_plugins(FoodPlugin)
         .add_plugins(CellPlugin)
+        .add_plugins(UserCellPlugin)
+        .add_plugins(StatsPlugin)
+        .add_plugins(StatsRecorderPlugin)
+        .add_plugins(VisualsPlugin)
+        .add_plugins(SnapshotPlugin {
+            resume_from: args.resume_from,
+        })
         .run();
 }
@@ -0,0 +1,930 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    brain::{BrainGraph, BrainModel, BrainNet, GraphEdge, GraphNode, MutationParams, NodeKind},
+    nn::{Activation, Crossover, NetError},
+    sim_config::SimConfig,
+    trackers::{on_sim_timer, SimSet},
+    *,
+};
+
+/// Version of the on-disk genome format written by [`Genome::save`]
+pub const GENOME_FILE_VERSION: u32 = 1;
+
+pub struct NeatPlugin;
+
+/// NEAT brain, a list of node and connection genes.
+/// Connection genes are lined up by their innovation number for crossover and speciation
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Genome {
+    num_inputs: usize,
+    num_outputs: usize,
+    /// Inputs first and outputs last, hidden nodes in between
+    nodes: Vec<NodeGene>,
+    /// Sorted by innovation number
+    connections: Vec<ConnectionGene>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct NodeGene {
+    id: u32,
+    kind: NodeKind,
+    bias: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ConnectionGene {
+    innovation: u32,
+    from: u32,
+    to: u32,
+    weight: f64,
+    enabled: bool,
+}
+
+/// Genome compiled for fast inference, with its nodes lined up in evaluation order.
+/// Values are indexed like the nodes of the genome, so the outputs come last
+#[derive(Clone)]
+pub struct FlatGenome {
+    num_inputs: usize,
+    num_values: usize,
+    /// Every node but the inputs, in evaluation order
+    nodes: Vec<FlatNode>,
+    /// Enabled connections into the nodes, as the index of the source node and the weight,
+    /// grouped in the order of `nodes`
+    incoming: Vec<(usize, f32)>,
+}
+
+#[derive(Clone)]
+struct FlatNode {
+    index: usize,
+    bias: f32,
+    num_incoming: usize,
+}
+
+/// On-disk representation of a genome
+#[derive(Serialize, Deserialize)]
+struct GenomeFile {
+    version: u32,
+    genome: Genome,
+}
+
+/// Hands out innovation numbers and node ids, so that the same structural
+/// mutation gets the same numbers in every genome
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct Innovations {
+    next_innovation: u32,
+    next_node: u32,
+    /// Keyed by `from << 32 | to`
+    connections: HashMap<u64, u32>,
+    /// Node created by splitting the connection of an innovation
+    splits: HashMap<u32, u32>,
+}
+
+/// Species of a cell with a NEAT brain
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct Species(pub u32);
+
+/// One representative genome per living species
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct SpeciesRegistry {
+    next_id: u32,
+    representatives: Vec<(u32, Genome)>,
+}
+
+/// NEAT bookkeeping shared by the systems that create brains
+#[derive(SystemParam)]
+pub struct NeatState<'w> {
+    pub innovations: ResMut<'w, Innovations>,
+    pub species: ResMut<'w, SpeciesRegistry>,
+}
+
+impl Plugin for NeatPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Innovations::default())
+            .insert_resource(SpeciesRegistry::default())
+            .add_systems(
+                FixedUpdate,
+                drop_extinct_species
+                    .run_if(on_sim_timer(5.0))
+                    .in_set(SimSet::PostPhysics),
+            );
+    }
+}
+
+/// Runs once the cells spawned in the tick have their `Species`
+fn drop_extinct_species(
+    mut species_registry: ResMut<SpeciesRegistry>,
+    species_query: Query<&Species>,
+) {
+    let living: HashSet<u32> = species_query.iter().map(|s| s.0).collect();
+    species_registry
+        .representatives
+        .retain(|(id, _)| living.contains(id));
+}
+
+impl NeatState<'_> {
+    /// Species of a new cell, `hint` is the species of its parent.
+    /// Dense brains aren't speciated
+    pub fn speciate(
+        &mut self,
+        brain: &BrainNet,
+        hint: Option<&Species>,
+        config: &SimConfig,
+    ) -> Option<Species> {
+        let BrainNet::Neat(genome) = brain else {
+            return None;
+        };
+
+        Some(Species(self.species.assign(
+            genome,
+            hint.map(|s| s.0),
+            config.neat_compatibility_threshold,
+        )))
+    }
+}
+
+impl Innovations {
+    fn connection(&mut self, from: u32, to: u32) -> u32 {
+        let key = (from as u64) << 32 | to as u64;
+        *self.connections.entry(key).or_insert_with(|| {
+            self.next_innovation += 1;
+            self.next_innovation
+        })
+    }
+
+    /// `first_hidden` is the id right after the input and output nodes
+    fn split(&mut self, innovation: u32, first_hidden: u32) -> u32 {
+        *self.splits.entry(innovation).or_insert_with(|| {
+            self.next_node = self.next_node.max(first_hidden);
+            self.next_node += 1;
+            self.next_node - 1
+        })
+    }
+}
+
+impl Genome {
+    /// Every input connected to every output, without hidden nodes
+    pub fn new(
+        num_inputs: usize,
+        num_outputs: usize,
+        innovations: &mut Innovations,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let mut nodes = Vec::new();
+        for id in 0..num_inputs {
+            nodes.push(NodeGene {
+                id: id as u32,
+                kind: NodeKind::Input,
+                bias: 0.0,
+            });
+        }
+        for id in num_inputs..num_inputs + num_outputs {
+            nodes.push(NodeGene {
+                id: id as u32,
+                kind: NodeKind::Output,
+                bias: rng.gen_range(-1.0..1.0),
+            });
+        }
+
+        let mut connections = Vec::new();
+        for from in 0..num_inputs as u32 {
+            for to in num_inputs as u32..(num_inputs + num_outputs) as u32 {
+                connections.push(ConnectionGene {
+                    innovation: innovations.connection(from, to),
+                    from,
+                    to,
+                    weight: rng.gen_range(-1.0..1.0),
+                    enabled: true,
+                });
+            }
+        }
+        connections.sort_by_key(|c| c.innovation);
+
+        Self {
+            num_inputs,
+            num_outputs,
+            nodes,
+            connections,
+        }
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.num_outputs
+    }
+
+    /// Excess, disjoint and weight differences, as in the NEAT paper
+    pub fn compatibility_distance(&self, other: &Genome) -> f64 {
+        let (a, b) = (&self.connections, &other.connections);
+        let (mut i, mut j) = (0, 0);
+        let (mut disjoint, mut matching, mut weight_diff) = (0, 0, 0.0);
+        while i < a.len() && j < b.len() {
+            if a[i].innovation == b[j].innovation {
+                matching += 1;
+                weight_diff += (a[i].weight - b[j].weight).abs();
+                i += 1;
+                j += 1;
+            } else if a[i].innovation < b[j].innovation {
+                disjoint += 1;
+                i += 1;
+            } else {
+                disjoint += 1;
+                j += 1;
+            }
+        }
+        let excess = (a.len() - i) + (b.len() - j);
+
+        // Small genomes aren't normalized, their few genes would weigh too much
+        let n = a.len().max(b.len());
+        let n = if n < 20 { 1.0 } else { n as f64 };
+        let mean_weight_diff = if matching > 0 {
+            weight_diff / matching as f64
+        } else {
+            0.0
+        };
+
+        NEAT_EXCESS_COEFF * excess as f64 / n
+            + NEAT_DISJOINT_COEFF * disjoint as f64 / n
+            + NEAT_WEIGHT_COEFF * mean_weight_diff
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), NetError> {
+        let file = GenomeFile {
+            version: GENOME_FILE_VERSION,
+            genome: self.clone(),
+        };
+        let contents = serde_json::to_string_pretty(&file)?;
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, NetError> {
+        let contents = fs::read_to_string(path)?;
+        let file: GenomeFile = serde_json::from_str(&contents)?;
+        if file.version != GENOME_FILE_VERSION {
+            return Err(NetError::UnsupportedVersion(file.version));
+        }
+
+        file.genome.validate()?;
+        Ok(file.genome)
+    }
+
+    /// Checks that the nodes are the inputs, then the hidden nodes, then the outputs,
+    /// and that every connection links two of them
+    pub fn validate(&self) -> Result<(), NetError> {
+        if self.nodes.len() < self.num_inputs + self.num_outputs {
+            return Err(NetError::BadShape(format!(
+                "{} nodes can't hold {} inputs and {} outputs",
+                self.nodes.len(),
+                self.num_inputs,
+                self.num_outputs
+            )));
+        }
+
+        let first_output = self.nodes.len() - self.num_outputs;
+        for (i, node) in self.nodes.iter().enumerate() {
+            let expected = match i {
+                _ if i < self.num_inputs => NodeKind::Input,
+                _ if i >= first_output => NodeKind::Output,
+                _ => NodeKind::Hidden,
+            };
+            if node.kind != expected {
+                return Err(NetError::BadShape(format!(
+                    "node {} is {:?}, expected {:?} at position {}",
+                    node.id, node.kind, expected, i
+                )));
+            }
+        }
+
+        let index = self.node_index();
+        if index.len() != self.nodes.len() {
+            return Err(NetError::BadShape("duplicate node ids".to_string()));
+        }
+        for c in self.connections.iter() {
+            if !index.contains_key(&c.from) || !index.contains_key(&c.to) {
+                return Err(NetError::BadShape(format!(
+                    "connection {} links {} to {}, a missing node",
+                    c.innovation, c.from, c.to
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Only needs rebuilding when the genome changes
+    pub fn to_flat(&self) -> FlatGenome {
+        let (order, incoming) = self.evaluation_order();
+        let nodes = order
+            .into_iter()
+            .filter(|i| self.nodes[*i].kind != NodeKind::Input)
+            .map(|i| FlatNode {
+                index: i,
+                bias: self.nodes[i].bias as f32,
+                num_incoming: incoming[i].len(),
+            });
+
+        let mut flat = FlatGenome {
+            num_inputs: self.num_inputs,
+            num_values: self.nodes.len(),
+            nodes: Vec::new(),
+            incoming: Vec::new(),
+        };
+        for node in nodes {
+            let links = incoming[node.index].iter();
+            flat.incoming
+                .extend(links.map(|(from, weight)| (*from, *weight as f32)));
+            flat.nodes.push(node);
+        }
+
+        flat
+    }
+
+    fn node_index(&self) -> HashMap<u32, usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.id, i))
+            .collect()
+    }
+
+    /// Node indices in evaluation order, along with the enabled connections into each node.
+    /// Nodes caught in a cycle, which crossover can bring in, are evaluated last
+    fn evaluation_order(&self) -> (Vec<usize>, Vec<Vec<(usize, f64)>>) {
+        let index = self.node_index();
+        let mut incoming = vec![Vec::new(); self.nodes.len()];
+        let mut outgoing = vec![Vec::new(); self.nodes.len()];
+        for c in self.connections.iter().filter(|c| c.enabled) {
+            let (from, to) = (index[&c.from], index[&c.to]);
+            incoming[to].push((from, c.weight));
+            outgoing[from].push(to);
+        }
+
+        let mut num_pending: Vec<usize> = incoming.iter().map(|i| i.len()).collect();
+        let mut ready: Vec<usize> = (0..self.nodes.len())
+            .filter(|i| num_pending[*i] == 0)
+            .rev()
+            .collect();
+        let mut order = Vec::new();
+        let mut is_done = vec![false; self.nodes.len()];
+        while let Some(i) = ready.pop() {
+            order.push(i);
+            is_done[i] = true;
+            for to in outgoing[i].iter() {
+                num_pending[*to] -= 1;
+                if num_pending[*to] == 0 {
+                    ready.push(*to);
+                }
+            }
+        }
+        order.extend((0..self.nodes.len()).filter(|i| !is_done[*i]));
+
+        (order, incoming)
+    }
+
+    fn has_path(&self, from: u32, to: u32) -> bool {
+        let mut stack = vec![from];
+        let mut seen = HashSet::new();
+        while let Some(id) = stack.pop() {
+            if id == to {
+                return true;
+            }
+            if !seen.insert(id) {
+                continue;
+            }
+            stack.extend(
+                self.connections
+                    .iter()
+                    .filter(|c| c.enabled && c.from == id)
+                    .map(|c| c.to),
+            );
+        }
+
+        false
+    }
+
+    fn insert_connection(&mut self, connection: ConnectionGene) {
+        let at = self
+            .connections
+            .partition_point(|c| c.innovation < connection.innovation);
+        self.connections.insert(at, connection);
+    }
+
+    /// Links two unconnected nodes, without closing a cycle
+    fn add_connection(&mut self, innovations: &mut Innovations, rng: &mut impl Rng) {
+        for _ in 0..20 {
+            let from = self.nodes.choose(rng).unwrap();
+            let to = self.nodes.choose(rng).unwrap();
+            if from.kind == NodeKind::Output || to.kind == NodeKind::Input || from.id == to.id {
+                continue;
+            }
+            let (from, to) = (from.id, to.id);
+            if self
+                .connections
+                .iter()
+                .any(|c| c.from == from && c.to == to)
+            {
+                continue;
+            }
+            if self.has_path(to, from) {
+                continue;
+            }
+
+            self.insert_connection(ConnectionGene {
+                innovation: innovations.connection(from, to),
+                from,
+                to,
+                weight: rng.gen_range(-1.0..1.0),
+                enabled: true,
+            });
+            return;
+        }
+    }
+
+    /// Splits a connection in two with a new hidden node in the middle,
+    /// the node starts out passing the signal through
+    fn add_node(&mut self, innovations: &mut Innovations, rng: &mut impl Rng) {
+        let enabled: Vec<usize> = (0..self.connections.len())
+            .filter(|i| self.connections[*i].enabled)
+            .collect();
+        let Some(&i) = enabled.choose(rng) else {
+            return;
+        };
+
+        let split = &self.connections[i];
+        let first_hidden = (self.num_inputs + self.num_outputs) as u32;
+        let node_id = innovations.split(split.innovation, first_hidden);
+        // Crossover can bring back a connection that this genome already split
+        if self.nodes.iter().any(|n| n.id == node_id) {
+            return;
+        }
+
+        let (from, to, weight) = (split.from, split.to, split.weight);
+        self.connections[i].enabled = false;
+        self.nodes.insert(
+            self.nodes.len() - self.num_outputs,
+            NodeGene {
+                id: node_id,
+                kind: NodeKind::Hidden,
+                bias: 0.0,
+            },
+        );
+        self.insert_connection(ConnectionGene {
+            innovation: innovations.connection(from, node_id),
+            from,
+            to: node_id,
+            weight: 1.0,
+            enabled: true,
+        });
+        self.insert_connection(ConnectionGene {
+            innovation: innovations.connection(node_id, to),
+            from: node_id,
+            to,
+            weight,
+            enabled: true,
+        });
+    }
+}
+
+impl BrainModel for Genome {
+    fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    fn activate(&self, inputs: &[f64]) -> Vec<f64> {
+        if inputs.len() != self.num_inputs {
+            panic!("Bad input size");
+        }
+
+        let (order, incoming) = self.evaluation_order();
+        let mut values = vec![0.0; self.nodes.len()];
+        values[..self.num_inputs].copy_from_slice(inputs);
+        for i in order {
+            let node = &self.nodes[i];
+            if node.kind == NodeKind::Input {
+                continue;
+            }
+            let total: f64 = node.bias
+                + incoming[i]
+                    .iter()
+                    .map(|(from, weight)| values[*from] * weight)
+                    .sum::<f64>();
+            values[i] = Activation::Sigmoid.apply(total);
+        }
+
+        values
+    }
+
+    fn mutate(
+        &mut self,
+        params: &MutationParams,
+        innovations: &mut Innovations,
+        rng: &mut impl Rng,
+    ) {
+        let weights = self.connections.iter_mut().map(|c| &mut c.weight);
+        let biases = self
+            .nodes
+            .iter_mut()
+            .filter(|n| n.kind != NodeKind::Input)
+            .map(|n| &mut n.bias);
+        for val in weights.chain(biases) {
+            if rng.gen_range(0.0..1.0) >= params.rate {
+                continue;
+            }
+
+            *val += rng.gen_range(-params.variation..params.variation) as f64;
+        }
+
+        if rng.gen_range(0.0..1.0) < params.add_connection_rate {
+            self.add_connection(innovations, rng);
+        }
+        if rng.gen_range(0.0..1.0) < params.add_node_rate {
+            self.add_node(innovations, rng);
+        }
+    }
+
+    /// Matching genes come from either parent, the disjoint and excess genes
+    /// from `self`, which is taken to be the fitter parent. `kind` doesn't apply to genomes
+    fn crossover(&self, other: &Self, _: Crossover, rng: &mut impl Rng) -> Result<Self, NetError> {
+        if self.num_inputs != other.num_inputs || self.num_outputs != other.num_outputs {
+            return Err(NetError::ArchMismatch {
+                expected: vec![self.num_inputs, self.num_outputs],
+                found: vec![other.num_inputs, other.num_outputs],
+            });
+        }
+
+        let mut child = self.clone();
+        let other_connections: HashMap<u32, &ConnectionGene> = other
+            .connections
+            .iter()
+            .map(|c| (c.innovation, c))
+            .collect();
+        for c in child.connections.iter_mut() {
+            let Some(other_c) = other_connections.get(&c.innovation) else {
+                continue;
+            };
+            if rng.gen_bool(0.5) {
+                c.weight = other_c.weight;
+            }
+            // Genes disabled in either parent usually stay disabled
+            if !c.enabled || !other_c.enabled {
+                c.enabled = rng.gen_bool(0.25);
+            }
+        }
+
+        let other_nodes: HashMap<u32, f64> = other.nodes.iter().map(|n| (n.id, n.bias)).collect();
+        for n in child.nodes.iter_mut() {
+            if let Some(bias) = other_nodes.get(&n.id) {
+                if rng.gen_bool(0.5) {
+                    n.bias = *bias;
+                }
+            }
+        }
+
+        Ok(child)
+    }
+
+    fn graph(&self) -> BrainGraph {
+        let (order, incoming) = self.evaluation_order();
+        let mut depths = vec![0; self.nodes.len()];
+        for i in order {
+            depths[i] = incoming[i]
+                .iter()
+                .map(|(from, _)| depths[*from] + 1)
+                .max()
+                .unwrap_or(0);
+        }
+        // Outputs share the last column, hidden nodes without inputs the first hidden one
+        let output_depth = depths.iter().max().copied().unwrap_or(0).max(1);
+        for (depth, node) in depths.iter_mut().zip(self.nodes.iter()) {
+            *depth = match node.kind {
+                NodeKind::Input => 0,
+                NodeKind::Hidden => (*depth).max(1),
+                NodeKind::Output => output_depth,
+            };
+        }
+
+        let index = self.node_index();
+        BrainGraph {
+            nodes: self
+                .nodes
+                .iter()
+                .zip(depths)
+                .map(|(n, depth)| GraphNode {
+                    kind: n.kind,
+                    depth,
+                    activation: match n.kind {
+                        NodeKind::Input => Activation::Identity,
+                        _ => Activation::Sigmoid,
+                    },
+                })
+                .collect(),
+            edges: self
+                .connections
+                .iter()
+                .filter(|c| c.enabled)
+                .map(|c| GraphEdge {
+                    from: index[&c.from],
+                    to: index[&c.to],
+                    weight: c.weight,
+                })
+                .collect(),
+        }
+    }
+
+    fn mean_abs_weight(&self) -> f64 {
+        let weights = self.connections.iter().map(|c| c.weight);
+        let biases = self
+            .nodes
+            .iter()
+            .filter(|n| n.kind != NodeKind::Input)
+            .map(|n| n.bias);
+        let (sum, count) = weights
+            .chain(biases)
+            .fold((0.0, 0), |(sum, count), w| (sum + w.abs(), count + 1));
+        sum / count.max(1) as f64
+    }
+}
+
+impl FlatGenome {
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    /// Values written by `predict_into`, one per node including the inputs
+    pub fn num_values(&self) -> usize {
+        self.num_values
+    }
+
+    /// Same values as `Genome::activate` within f32 precision,
+    /// written to `values` without allocating
+    pub fn predict_into(&self, inputs: &[f64], values: &mut [f32]) {
+        if inputs.len() != self.num_inputs || values.len() != self.num_values {
+            panic!("Bad input size");
+        }
+
+        for (value, input) in values.iter_mut().zip(inputs) {
+            *value = *input as f32;
+        }
+        // Nodes caught in a cycle read the others before they are evaluated
+        values[self.num_inputs..].fill(0.0);
+        let mut incoming = self.incoming.as_slice();
+        for node in self.nodes.iter() {
+            let (links, next) = incoming.split_at(node.num_incoming);
+            let total = node.bias
+                + links
+                    .iter()
+                    .map(|(from, weight)| values[*from] * weight)
+                    .sum::<f32>();
+            values[node.index] = Activation::Sigmoid.apply(total as f64) as f32;
+            incoming = next;
+        }
+    }
+}
+
+impl SpeciesRegistry {
+    /// Species of the first representative within `threshold` of `genome`, starting
+    /// with the `hint`, usually the species of the parent. Founds a new one when none is
+    pub fn assign(&mut self, genome: &Genome, hint: Option<u32>, threshold: f64) -> u32 {
+        let hinted = self
+            .representatives
+            .iter()
+            .filter(|(id, _)| Some(*id) == hint);
+        let others = self
+            .representatives
+            .iter()
+            .filter(|(id, _)| Some(*id) != hint);
+        if let Some((id, _)) = hinted
+            .chain(others)
+            .find(|(_, rep)| rep.compatibility_distance(genome) < threshold)
+        {
+            return *id;
+        }
+
+        self.next_id += 1;
+        self.representatives.push((self.next_id, genome.clone()));
+        self.next_id
+    }
+
+    pub fn len(&self) -> usize {
+        self.representatives.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.representatives.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    const NUM_INPUTS: usize = 4;
+    const NUM_OUTPUTS: usize = 3;
+
+    /// Genome with a few dozen structural mutations on top of the initial one
+    fn grown(seed: u64, innovations: &mut Innovations) -> Genome {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut genome = Genome::new(NUM_INPUTS, NUM_OUTPUTS, innovations, &mut rng);
+        for _ in 0..30 {
+            genome.add_node(innovations, &mut rng);
+            genome.add_connection(innovations, &mut rng);
+        }
+        genome
+    }
+
+    fn innovation_numbers(genome: &Genome) -> Vec<u32> {
+        genome.connections.iter().map(|c| c.innovation).collect()
+    }
+
+    fn has_cycle(genome: &Genome) -> bool {
+        genome
+            .connections
+            .iter()
+            .filter(|c| c.enabled)
+            .any(|c| genome.has_path(c.to, c.from))
+    }
+
+    #[test]
+    fn same_mutation_gets_the_same_innovation() {
+        let mut innovations = Innovations::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut a = Genome::new(1, 1, &mut innovations, &mut rng);
+        let mut b = Genome::new(1, 1, &mut innovations, &mut rng);
+        assert_eq!(innovation_numbers(&a), innovation_numbers(&b));
+
+        // A single connection to split, so both genomes make the same mutation
+        a.add_node(&mut innovations, &mut rng);
+        b.add_node(&mut innovations, &mut rng);
+        assert_eq!(innovation_numbers(&a), innovation_numbers(&b));
+        let ids = |g: &Genome| g.nodes.iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids(&a), ids(&b));
+
+        // A genome founded later still splits the connection into the same node
+        let mut c = Genome::new(1, 1, &mut innovations, &mut rng);
+        c.add_node(&mut innovations, &mut rng);
+        assert_eq!(innovation_numbers(&c), innovation_numbers(&a));
+        assert_eq!(ids(&c), ids(&a));
+
+        let newest = *innovation_numbers(&a).iter().max().unwrap();
+        assert!(innovations.connection(2, 0) > newest);
+    }
+
+    #[test]
+    fn add_node_splits_a_connection() {
+        let mut innovations = Innovations::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let mut genome = Genome::new(NUM_INPUTS, NUM_OUTPUTS, &mut innovations, &mut rng);
+        let before = genome.clone();
+        genome.add_node(&mut innovations, &mut rng);
+
+        let split = genome
+            .connections
+            .iter()
+            .find(|c| !c.enabled)
+            .expect("the split connection is disabled");
+        let new_node = &genome.nodes[NUM_INPUTS];
+        assert_eq!(new_node.kind, NodeKind::Hidden);
+        assert_eq!(genome.nodes.len(), before.nodes.len() + 1);
+        assert_eq!(genome.connections.len(), before.connections.len() + 2);
+
+        let find = |from, to| {
+            genome
+                .connections
+                .iter()
+                .find(|c| c.from == from && c.to == to && c.enabled)
+                .expect("the new node is linked on both sides")
+        };
+        assert_eq!(find(split.from, new_node.id).weight, 1.0);
+        assert_eq!(find(new_node.id, split.to).weight, split.weight);
+    }
+
+    #[test]
+    fn add_node_keeps_the_outputs_last() {
+        let mut innovations = Innovations::default();
+        let genome = grown(3, &mut innovations);
+        assert!(genome.nodes.len() > NUM_INPUTS + NUM_OUTPUTS);
+        assert!(genome.validate().is_ok());
+        let outputs = &genome.nodes[genome.nodes.len() - NUM_OUTPUTS..];
+        assert!(outputs.iter().all(|n| n.kind == NodeKind::Output));
+        assert!(genome
+            .connections
+            .windows(2)
+            .all(|w| w[0].innovation < w[1].innovation));
+    }
+
+    #[test]
+    fn add_connection_never_closes_a_cycle() {
+        let mut innovations = Innovations::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let mut genome = Genome::new(NUM_INPUTS, NUM_OUTPUTS, &mut innovations, &mut rng);
+        for _ in 0..200 {
+            if rng.gen_bool(0.3) {
+                genome.add_node(&mut innovations, &mut rng);
+            }
+            genome.add_connection(&mut innovations, &mut rng);
+            assert!(!has_cycle(&genome));
+        }
+
+        let index = genome.node_index();
+        for c in genome.connections.iter() {
+            assert_ne!(genome.nodes[index[&c.from]].kind, NodeKind::Output);
+            assert_ne!(genome.nodes[index[&c.to]].kind, NodeKind::Input);
+        }
+    }
+
+    #[test]
+    fn crossover_keeps_the_shared_genes() {
+        let mut innovations = Innovations::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let a = grown(6, &mut innovations);
+        let b = grown(7, &mut innovations);
+        let child = BrainModel::crossover(&a, &b, Crossover::Uniform, &mut rng).unwrap();
+
+        // Disjoint and excess genes come from the first parent only
+        assert_eq!(innovation_numbers(&child), innovation_numbers(&a));
+        let b_connections: HashMap<u32, &ConnectionGene> =
+            b.connections.iter().map(|c| (c.innovation, c)).collect();
+        let mut num_shared = 0;
+        for (c, a_c) in child.connections.iter().zip(a.connections.iter()) {
+            assert_eq!((c.from, c.to), (a_c.from, a_c.to));
+            match b_connections.get(&c.innovation) {
+                Some(b_c) => {
+                    num_shared += 1;
+                    assert!(c.weight == a_c.weight || c.weight == b_c.weight);
+                }
+                None => assert_eq!(c.weight, a_c.weight),
+            }
+        }
+        assert!(num_shared >= NUM_INPUTS * NUM_OUTPUTS);
+        assert!(child.validate().is_ok());
+    }
+
+    #[test]
+    fn identical_genomes_are_zero_apart() {
+        let mut innovations = Innovations::default();
+        let a = grown(8, &mut innovations);
+        let b = grown(9, &mut innovations);
+        assert_eq!(a.compatibility_distance(&a.clone()), 0.0);
+        assert_eq!(b.compatibility_distance(&b), 0.0);
+        assert!(a.compatibility_distance(&b) > 0.0);
+        assert_eq!(a.compatibility_distance(&b), b.compatibility_distance(&a));
+    }
+
+    #[test]
+    fn flat_genome_matches_activate() {
+        let mut innovations = Innovations::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(10);
+        let genome = grown(11, &mut innovations);
+        let flat = genome.to_flat();
+        let mut values = vec![0.0; flat.num_values()];
+        for _ in 0..50 {
+            let inputs: Vec<f64> = (0..NUM_INPUTS).map(|_| rng.gen_range(-1.0..1.0)).collect();
+            let expected = genome.activate(&inputs);
+            flat.predict_into(&inputs, &mut values);
+            for (e, v) in expected.iter().zip(values.iter()) {
+                assert!((e - *v as f64).abs() < 1e-4, "expected {}, found {}", e, v);
+            }
+        }
+    }
+
+    #[test]
+    fn load_rejects_malformed_genomes() {
+        let mut innovations = Innovations::default();
+        let genome = grown(12, &mut innovations);
+        let path = env::temp_dir()
+            .join(format!("ava-neat-{}", std::process::id()))
+            .join("genome.json");
+        genome.save(&path).unwrap();
+        assert!(Genome::load(&path).is_ok());
+
+        let mut dangling = genome.clone();
+        dangling.connections[0].to = 999;
+        let mut extra_input = genome.clone();
+        extra_input.num_inputs += 1;
+        let mut outputs_first = genome.clone();
+        let output = outputs_first.nodes.pop().unwrap();
+        outputs_first.nodes.insert(NUM_INPUTS, output);
+        for bad in [dangling, extra_input, outputs_first] {
+            bad.save(&path).unwrap();
+            assert!(matches!(Genome::load(&path), Err(NetError::BadShape(_))));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
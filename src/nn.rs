@@ -1,23 +1,91 @@
+use std::{fmt, fs, io, path::Path};
+
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::*;
+use crate::{
+    brain::{BrainGraph, BrainModel, GraphEdge, GraphNode, MutationParams, NodeKind},
+    neat::Innovations,
+};
 
-#[derive(Clone)]
+/// Version of the on-disk brain format written by [`Net::save`]
+pub const NET_FILE_VERSION: u32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Net {
     n_inputs: usize,
     layers: Vec<Layer>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 struct Layer {
     nodes: Vec<Vec<f64>>,
+    /// Brains saved before activations were selectable are all sigmoid
+    #[serde(default)]
+    activation: Activation,
+}
+
+/// Function applied to the weighted sum of every node of a layer
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Activation {
+    #[default]
+    Sigmoid,
+    Tanh,
+    Relu,
+    LeakyRelu,
+    Identity,
+    Step,
+}
+
+/// Dense net compiled to one contiguous buffer of f32 weights for fast inference.
+/// Every node is stored as its bias followed by one weight per node of the previous layer
+#[derive(Clone)]
+pub struct FlatNet {
+    layer_sizes: Vec<usize>,
+    activations: Vec<Activation>,
+    weights: Vec<f32>,
+}
+
+/// On-disk representation of a brain
+#[derive(Serialize, Deserialize)]
+struct NetFile {
+    version: u32,
+    layer_sizes: Vec<usize>,
+    net: Net,
+}
+
+/// How the weights of two parents are combined by [`Net::crossover`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Crossover {
+    /// Every weight comes from either parent
+    Uniform,
+    /// Every neuron keeps all of its weights from either parent
+    PerNeuron,
+    /// Weights before a random cut come from the first parent, the rest from the second
+    SinglePoint,
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    UnsupportedVersion(u32),
+    BadShape(String),
+    ArchMismatch {
+        expected: Vec<usize>,
+        found: Vec<usize>,
+    },
 }
 
 impl Net {
-    pub fn new(layer_sizes: Vec<usize>) -> Self {
+    /// `activations` has one entry per layer after the input layer
+    pub fn new(layer_sizes: Vec<usize>, activations: &[Activation], rng: &mut impl Rng) -> Self {
         if layer_sizes.len() < 2 {
             panic!("Need at least 2 layers");
         }
+        if activations.len() != layer_sizes.len() - 1 {
+            panic!("Need one activation per layer");
+        }
         for &size in layer_sizes.iter() {
             if size < 1 {
                 panic!("Empty layers not allowed");
@@ -28,8 +96,8 @@ impl Net {
         let first_layer_size = *layer_sizes.first().unwrap();
         let mut prev_layer_size = first_layer_size;
 
-        for &layer_size in layer_sizes[1..].iter() {
-            layers.push(Layer::new(layer_size, prev_layer_size));
+        for (&layer_size, &activation) in layer_sizes[1..].iter().zip(activations) {
+            layers.push(Layer::new(layer_size, prev_layer_size, activation, rng));
             prev_layer_size = layer_size;
         }
 
@@ -54,14 +122,309 @@ impl Net {
         outputs
     }
 
-    pub fn mutate(&mut self) {
-        self.layers.iter_mut().for_each(|l| l.mutate());
+    /// Nudges a `rate` fraction of the weights by up to `variation`
+    pub fn mutate(&mut self, rate: f32, variation: f32, rng: &mut impl Rng) {
+        self.layers
+            .iter_mut()
+            .for_each(|l| l.mutate(rate, variation, rng));
+    }
+
+    /// Gives a random hidden layer a random activation, with a chance of `rate`.
+    /// The output layer keeps its activation, the action thresholds and the memory rely on its range
+    pub fn mutate_activation(&mut self, rate: f32, rng: &mut impl Rng) {
+        let num_hidden = self.layers.len() - 1;
+        if num_hidden == 0 || rng.gen_range(0.0..1.0) >= rate {
+            return;
+        }
+
+        let layer_index = rng.gen_range(0..num_hidden);
+        self.layers[layer_index].activation =
+            Activation::ALL[rng.gen_range(0..Activation::ALL.len())];
+    }
+
+    pub fn to_flat(&self) -> FlatNet {
+        FlatNet {
+            layer_sizes: self.layer_sizes(),
+            activations: self.activations(),
+            weights: self
+                .layers
+                .iter()
+                .flat_map(|l| l.nodes.iter().flatten())
+                .map(|w| *w as f32)
+                .collect(),
+        }
+    }
+
+    /// Activation of every layer after the input layer
+    pub fn activations(&self) -> Vec<Activation> {
+        self.layers.iter().map(|l| l.activation).collect()
+    }
+
+    /// Child of `self` and `other`, both need the same layer sizes.
+    /// The child keeps the activations of `self`
+    pub fn crossover(
+        &self,
+        other: &Net,
+        kind: Crossover,
+        rng: &mut impl Rng,
+    ) -> Result<Net, NetError> {
+        if self.layer_sizes() != other.layer_sizes() {
+            return Err(NetError::ArchMismatch {
+                expected: self.layer_sizes(),
+                found: other.layer_sizes(),
+            });
+        }
+
+        let cut = match kind {
+            Crossover::SinglePoint => {
+                let num_weights: usize = self
+                    .layers
+                    .iter()
+                    .flat_map(|l| l.nodes.iter())
+                    .map(|n| n.len())
+                    .sum();
+                rng.gen_range(0..=num_weights)
+            }
+            _ => 0,
+        };
+        let mut weight_index = 0;
+
+        let mut child = self.clone();
+        let nodes = child.layers.iter_mut().flat_map(|l| l.nodes.iter_mut());
+        let other_nodes = other.layers.iter().flat_map(|l| l.nodes.iter());
+        for (node, other_node) in nodes.zip(other_nodes) {
+            match kind {
+                Crossover::Uniform => {
+                    for (w, other_w) in node.iter_mut().zip(other_node.iter()) {
+                        if rng.gen_bool(0.5) {
+                            *w = *other_w;
+                        }
+                    }
+                }
+                Crossover::PerNeuron => {
+                    if rng.gen_bool(0.5) {
+                        node.clone_from(other_node);
+                    }
+                }
+                Crossover::SinglePoint => {
+                    for (w, other_w) in node.iter_mut().zip(other_node.iter()) {
+                        if weight_index >= cut {
+                            *w = *other_w;
+                        }
+                        weight_index += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(child)
+    }
+
+    /// Sizes of every layer, starting with the input layer
+    pub fn layer_sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![self.n_inputs];
+        sizes.extend(self.layers.iter().map(|l| l.nodes.len()));
+        sizes
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), NetError> {
+        let file = NetFile {
+            version: NET_FILE_VERSION,
+            layer_sizes: self.layer_sizes(),
+            net: self.clone(),
+        };
+        let contents = serde_json::to_string_pretty(&file)?;
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, NetError> {
+        let contents = fs::read_to_string(path)?;
+        let file: NetFile = serde_json::from_str(&contents)?;
+        if file.version != NET_FILE_VERSION {
+            return Err(NetError::UnsupportedVersion(file.version));
+        }
+
+        file.net.validate()?;
+        if file.net.layer_sizes() != file.layer_sizes {
+            return Err(NetError::BadShape(format!(
+                "declared layer sizes {:?} don't match weights {:?}",
+                file.layer_sizes,
+                file.net.layer_sizes()
+            )));
+        }
+
+        Ok(file.net)
+    }
+
+    /// Checks that every node has one weight per input plus a bias
+    pub fn validate(&self) -> Result<(), NetError> {
+        if self.layers.is_empty() {
+            return Err(NetError::BadShape("need at least 2 layers".to_string()));
+        }
+
+        let mut prev_layer_size = self.n_inputs;
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            if layer.nodes.is_empty() {
+                return Err(NetError::BadShape(format!(
+                    "layer {} is empty",
+                    layer_index + 1
+                )));
+            }
+            for node in layer.nodes.iter() {
+                if node.len() != prev_layer_size + 1 {
+                    return Err(NetError::BadShape(format!(
+                        "layer {} expects {} weights per node, found {}",
+                        layer_index + 1,
+                        prev_layer_size + 1,
+                        node.len()
+                    )));
+                }
+            }
+            prev_layer_size = layer.nodes.len();
+        }
+
+        Ok(())
     }
 }
 
+impl BrainModel for Net {
+    fn num_inputs(&self) -> usize {
+        self.n_inputs
+    }
+
+    fn activate(&self, inputs: &[f64]) -> Vec<f64> {
+        self.predict(&inputs.to_vec()).concat()
+    }
+
+    /// Dense nets keep their layers, only the weights are mutated
+    fn mutate(&mut self, params: &MutationParams, _: &mut Innovations, rng: &mut impl Rng) {
+        Net::mutate(self, params.rate, params.variation, rng);
+        self.mutate_activation(params.activation_rate, rng);
+    }
+
+    fn crossover(
+        &self,
+        other: &Self,
+        kind: Crossover,
+        rng: &mut impl Rng,
+    ) -> Result<Self, NetError> {
+        Net::crossover(self, other, kind, rng)
+    }
+
+    fn graph(&self) -> BrainGraph {
+        let mut graph = BrainGraph::default();
+        let layer_sizes = self.layer_sizes();
+        let mut layer_start = 0;
+        for (depth, size) in layer_sizes.iter().enumerate() {
+            let kind = match depth {
+                0 => NodeKind::Input,
+                _ if depth == layer_sizes.len() - 1 => NodeKind::Output,
+                _ => NodeKind::Hidden,
+            };
+            let activation = match depth {
+                0 => Activation::Identity,
+                _ => self.layers[depth - 1].activation,
+            };
+            graph.nodes.extend((0..*size).map(|_| GraphNode {
+                kind,
+                depth,
+                activation,
+            }));
+
+            if depth > 0 {
+                let prev_start = layer_start - layer_sizes[depth - 1];
+                for (i, node) in self.layers[depth - 1].nodes.iter().enumerate() {
+                    // The first weight of a node is its bias
+                    for (j, weight) in node.iter().skip(1).enumerate() {
+                        graph.edges.push(GraphEdge {
+                            from: prev_start + j,
+                            to: layer_start + i,
+                            weight: *weight,
+                        });
+                    }
+                }
+            }
+            layer_start += size;
+        }
+
+        graph
+    }
+
+    fn mean_abs_weight(&self) -> f64 {
+        let (sum, count) = self
+            .layers
+            .iter()
+            .flat_map(|l| l.nodes.iter().flatten())
+            .fold((0.0, 0), |(sum, count), w| (sum + w.abs(), count + 1));
+        sum / count as f64
+    }
+}
+
+impl FlatNet {
+    pub fn num_inputs(&self) -> usize {
+        self.layer_sizes[0]
+    }
+
+    /// Values written by `predict_into`, one per node including the inputs
+    pub fn num_values(&self) -> usize {
+        self.layer_sizes.iter().sum()
+    }
+
+    /// Same values as `Net::predict(..).concat()` within f32 precision,
+    /// written to `values` without allocating
+    pub fn predict_into(&self, inputs: &[f64], values: &mut [f32]) {
+        if inputs.len() != self.num_inputs() || values.len() != self.num_values() {
+            panic!("Bad input size");
+        }
+
+        for (value, input) in values.iter_mut().zip(inputs) {
+            *value = *input as f32;
+        }
+        let mut weights = self.weights.as_slice();
+        let mut layer_start = 0;
+        for (sizes, activation) in self.layer_sizes.windows(2).zip(self.activations.iter()) {
+            let (prev_layer, rest) = values[layer_start..].split_at_mut(sizes[0]);
+            for value in rest[..sizes[1]].iter_mut() {
+                let (node, next) = weights.split_at(sizes[0] + 1);
+                let total = node[0] + dot_prod(&node[1..], prev_layer);
+                *value = activation.apply(total as f64) as f32;
+                weights = next;
+            }
+            layer_start += sizes[0];
+        }
+    }
+}
+
+/// Accumulates in 8 lanes so that the compiler can vectorize it
+fn dot_prod(weights: &[f32], values: &[f32]) -> f32 {
+    let mut lanes = [0f32; 8];
+    for (w, v) in weights.chunks_exact(8).zip(values.chunks_exact(8)) {
+        for ((lane, w), v) in lanes.iter_mut().zip(w).zip(v) {
+            *lane += w * v;
+        }
+    }
+    let tail = weights.len() / 8 * 8;
+    let tail: f32 = weights[tail..]
+        .iter()
+        .zip(&values[tail..])
+        .map(|(w, v)| w * v)
+        .sum();
+
+    lanes.iter().sum::<f32>() + tail
+}
+
 impl Layer {
-    fn new(layer_size: usize, prev_layer_size: usize) -> Self {
-        let mut rng = rand::thread_rng();
+    fn new(
+        layer_size: usize,
+        prev_layer_size: usize,
+        activation: Activation,
+        rng: &mut impl Rng,
+    ) -> Self {
         let mut nodes: Vec<Vec<f64>> = Vec::new();
 
         for _ in 0..layer_size {
@@ -73,27 +436,26 @@ impl Layer {
             nodes.push(node);
         }
 
-        Self { nodes }
+        Self { nodes, activation }
     }
 
     fn predict(&self, inputs: &Vec<f64>) -> Vec<f64> {
         let mut layer_results = Vec::new();
         for node in self.nodes.iter() {
-            layer_results.push(self.sigmoid(self.dot_prod(&node, &inputs)));
+            layer_results.push(self.activation.apply(self.dot_prod(&node, &inputs)));
         }
 
         layer_results
     }
 
-    fn mutate(&mut self) {
-        let mut rng = rand::thread_rng();
+    fn mutate(&mut self, rate: f32, variation: f32, rng: &mut impl Rng) {
         for n in self.nodes.iter_mut() {
             for val in n.iter_mut() {
-                if rng.gen_range(0.0..1.0) >= BRAIN_MUTATION_RATE {
+                if rng.gen_range(0.0..1.0) >= rate {
                     continue;
                 }
 
-                *val += rng.gen_range(-BRAIN_MUTATION_VARIATION..BRAIN_MUTATION_VARIATION) as f64;
+                *val += rng.gen_range(-variation..variation) as f64;
             }
         }
     }
@@ -107,8 +469,226 @@ impl Layer {
 
         total
     }
+}
+
+impl Activation {
+    pub const ALL: [Activation; 6] = [
+        Activation::Sigmoid,
+        Activation::Tanh,
+        Activation::Relu,
+        Activation::LeakyRelu,
+        Activation::Identity,
+        Activation::Step,
+    ];
+
+    pub fn apply(&self, y: f64) -> f64 {
+        match self {
+            Activation::Sigmoid => 1f64 / (1f64 + (-y).exp()),
+            Activation::Tanh => y.tanh(),
+            Activation::Relu => y.max(0.0),
+            Activation::LeakyRelu => {
+                if y > 0.0 {
+                    y
+                } else {
+                    0.01 * y
+                }
+            }
+            Activation::Identity => y,
+            Activation::Step => {
+                if y > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Output for a zero input, values above it count as the node firing
+    pub fn midpoint(&self) -> f64 {
+        self.apply(0.0)
+    }
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Io(e) => write!(f, "io error: {}", e),
+            NetError::Parse(e) => write!(f, "parse error: {}", e),
+            NetError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported brain file version {}, expected {}",
+                v, NET_FILE_VERSION
+            ),
+            NetError::BadShape(msg) => write!(f, "bad network shape: {}", msg),
+            NetError::ArchMismatch { expected, found } => {
+                write!(f, "layer sizes {:?} don't match {:?}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> Self {
+        NetError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for NetError {
+    fn from(e: serde_json::Error) -> Self {
+        NetError::Parse(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir()
+            .join(format!("ava-nn-{}", std::process::id()))
+            .join(name)
+    }
+
+    fn write_file(name: &str, file: &NetFile) -> PathBuf {
+        let path = temp_path(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, serde_json::to_string(file).unwrap()).unwrap();
+        path
+    }
+
+    fn weight_bits(net: &Net) -> Vec<u64> {
+        net.layers
+            .iter()
+            .flat_map(|l| l.nodes.iter().flatten())
+            .map(|w| w.to_bits())
+            .collect()
+    }
+
+    fn random_net(rng: &mut ChaCha8Rng) -> Net {
+        Net::new(
+            vec![12, 8, 6, 4],
+            &[Activation::Tanh, Activation::Relu, Activation::Sigmoid],
+            rng,
+        )
+    }
+
+    #[test]
+    fn save_and_load_round_trip_bit_exact() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut net = random_net(&mut rng);
+        net.mutate(0.5, 0.3, &mut rng);
+        let path = temp_path("round_trip.json");
+        net.save(&path).unwrap();
+        let loaded = Net::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.layer_sizes(), net.layer_sizes());
+        assert_eq!(loaded.activations(), net.activations());
+        assert_eq!(weight_bits(&loaded), weight_bits(&net));
+        for _ in 0..20 {
+            let inputs: Vec<f64> = (0..12).map(|_| rng.gen_range(-1.0..1.0)).collect();
+            let expected: Vec<u64> = net
+                .predict(&inputs)
+                .concat()
+                .iter()
+                .map(|v| v.to_bits())
+                .collect();
+            let found: Vec<u64> = loaded
+                .predict(&inputs)
+                .concat()
+                .iter()
+                .map(|v| v.to_bits())
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn activation_mutation_keeps_the_output_layer() {
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let mut net = random_net(&mut rng);
+        for _ in 0..100 {
+            net.mutate_activation(1.0, &mut rng);
+            assert_eq!(net.activations().last(), Some(&Activation::Sigmoid));
+        }
+        assert_ne!(net.activations()[..2], [Activation::Tanh, Activation::Relu]);
+
+        let mut no_hidden = Net::new(vec![3, 2], &[Activation::Tanh], &mut rng);
+        no_hidden.mutate_activation(1.0, &mut rng);
+        assert_eq!(no_hidden.activations(), vec![Activation::Tanh]);
+    }
+
+    #[test]
+    fn load_rejects_other_versions() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let net = random_net(&mut rng);
+        let path = write_file(
+            "version.json",
+            &NetFile {
+                version: NET_FILE_VERSION + 1,
+                layer_sizes: net.layer_sizes(),
+                net,
+            },
+        );
+
+        let result = Net::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            result,
+            Err(NetError::UnsupportedVersion(v)) if v == NET_FILE_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn load_rejects_bad_shapes() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let net = random_net(&mut rng);
+        let path = write_file(
+            "declared.json",
+            &NetFile {
+                version: NET_FILE_VERSION,
+                layer_sizes: vec![12, 8, 4],
+                net: net.clone(),
+            },
+        );
+        let result = Net::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(NetError::BadShape(_))));
+
+        let mut truncated = net.clone();
+        truncated.layers[1].nodes[0].pop();
+        let path = write_file(
+            "truncated.json",
+            &NetFile {
+                version: NET_FILE_VERSION,
+                layer_sizes: net.layer_sizes(),
+                net: truncated,
+            },
+        );
+        let result = Net::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(NetError::BadShape(_))));
 
-    fn sigmoid(&self, y: f64) -> f64 {
-        1f64 / (1f64 + (-y).exp())
+        let mut empty = net.clone();
+        empty.layers[0].nodes.clear();
+        let path = write_file(
+            "empty.json",
+            &NetFile {
+                version: NET_FILE_VERSION,
+                layer_sizes: net.layer_sizes(),
+                net: empty,
+            },
+        );
+        let result = Net::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(NetError::BadShape(_))));
     }
 }
@@ -1,72 +1,231 @@
-use std::time::Instant;
-
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashSet};
 use bevy_rapier2d::prelude::*;
 
-use crate::{bullet::Bullet, cell::energy::EnergyMap, food::Food, settings::DynamicSettings, *};
+use crate::{
+    boundary::{BoundaryPlugin, Wall},
+    bullet::{Bullet, BulletOwner},
+    cell::{
+        energy::{Energy, EnergyLedger, EnergyReason},
+        traits::BodyTraits,
+        user::UserControlledCell,
+        Cell, KilledInCombat, Lineage,
+    },
+    events::FoodEaten,
+    food::Food,
+    settings::DynamicSettings,
+    sim_config::SimConfig,
+    trackers::{is_sim_running, SimSet},
+    *,
+};
 
 pub struct PhysicsPlugin;
 
+type RapierPlugin = RapierPhysicsPlugin<NoUserData>;
+/// The user cell neither gains nor loses energy, bullets go through it
+type CollidingCell<'a> = (
+    &'a Cell,
+    &'a Lineage,
+    &'a BodyTraits,
+    (&'a mut Energy, &'a mut EnergyLedger),
+);
+
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+        // Physics is stepped on the simulation tick instead of every frame
+        app.add_plugins(RapierPlugin::pixels_per_meter(100.0).with_default_system_setup(false))
             // .add_plugins(RapierDebugRenderPlugin::default())
+            .configure_sets(
+                FixedUpdate,
+                (
+                    SimSet::Logic,
+                    PhysicsSet::SyncBackend,
+                    PhysicsSet::SyncBackendFlush,
+                    PhysicsSet::StepSimulation,
+                    PhysicsSet::Writeback,
+                    SimSet::PostPhysics,
+                )
+                    .chain(),
+            )
+            .configure_sets(
+                FixedUpdate,
+                (
+                    PhysicsSet::SyncBackend.run_if(is_sim_running),
+                    PhysicsSet::SyncBackendFlush.run_if(is_sim_running),
+                    PhysicsSet::StepSimulation.run_if(is_sim_running),
+                    PhysicsSet::Writeback.run_if(is_sim_running),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    RapierPlugin::get_systems(PhysicsSet::SyncBackend)
+                        .in_set(PhysicsSet::SyncBackend),
+                    RapierPlugin::get_systems(PhysicsSet::SyncBackendFlush)
+                        .in_set(PhysicsSet::SyncBackendFlush),
+                    RapierPlugin::get_systems(PhysicsSet::StepSimulation)
+                        .in_set(PhysicsSet::StepSimulation),
+                    RapierPlugin::get_systems(PhysicsSet::Writeback).in_set(PhysicsSet::Writeback),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                scale_new_colliders
+                    .after(SimSet::Logic)
+                    .before(PhysicsSet::SyncBackend)
+                    .run_if(is_sim_running),
+            )
+            .add_plugins(BoundaryPlugin)
+            .add_event::<FoodEaten>()
             .add_systems(Startup, setup)
-            .add_systems(Update, handle_collision_events);
+            .add_systems(
+                FixedUpdate,
+                (handle_collision_events, update_collision_groups).in_set(SimSet::PostPhysics),
+            );
     }
 }
 
 fn setup(mut rapier_config: ResMut<RapierConfiguration>) {
     rapier_config.gravity = Vec2::ZERO;
+    rapier_config.timestep_mode = TimestepMode::Fixed {
+        dt: SIM_TICK_SECS,
+        substeps: 1,
+    };
+}
+
+/// Rapier only scales a collider by its transform once it's in the physics world, new bodies
+/// would take their first step at the unscaled size and restored ones would drift off
+fn scale_new_colliders(
+    rapier_config: Res<RapierConfiguration>,
+    mut collider_query: Query<(&mut Collider, &Transform), Added<Collider>>,
+) {
+    for (mut collider, transform) in collider_query.iter_mut() {
+        collider.set_scale(
+            transform.scale.truncate(),
+            rapier_config.scaled_shape_subdivision,
+        );
+    }
 }
 
 fn handle_collision_events(
     mut commands: Commands,
-    mut energy_map: ResMut<EnergyMap>,
+    config: Res<SimConfig>,
     settings: Res<DynamicSettings>,
-    food_query: Query<With<Food>>,
-    bullet_query: Query<&Bullet, With<Bullet>>,
+    mut food_writer: EventWriter<FoodEaten>,
+    food_query: Query<&Transform, With<Food>>,
+    bullet_query: Query<(&Bullet, &BulletOwner)>,
+    mut cell_query: Query<CollidingCell, Without<UserControlledCell>>,
+    wall_query: Query<(), With<Wall>>,
     mut collision_events: EventReader<CollisionEvent>,
 ) {
+    // A bullet can touch several entities in the same step but only hits once
+    let mut spent_bullets = HashSet::new();
+
     for collision_event in collision_events.iter() {
-        match collision_event {
-            CollisionEvent::Started(e1, e2, _) => {
-                if let Ok(b) = bullet_query.get(*e1) {
-                    if let Ok(_) = food_query.get(*e2) {
-                        commands.entity(*e2).despawn();
-                        commands.entity(*e1).despawn();
-                        match energy_map.0.get_key_value_mut(&(b.0)) {
-                            Some((_, (v, i))) => {
-                                *v = MAX_ENERGY.min(*v + settings.energy_per_food);
-                                *i = Instant::now();
-                            }
-                            None => {
-                                energy_map
-                                    .0
-                                    .insert(b.0, (settings.energy_per_food, Instant::now()));
-                            }
-                        }
-                    }
-                }
-                if let Ok(b) = bullet_query.get(*e2) {
-                    if let Ok(_) = food_query.get(*e1) {
-                        commands.entity(*e1).despawn();
-                        commands.entity(*e2).despawn();
-                        match energy_map.0.get_key_value_mut(&(b.0)) {
-                            Some((_, (v, i))) => {
-                                *v = MAX_ENERGY.min(*v + settings.energy_per_food);
-                                *i = Instant::now();
-                            }
-                            None => {
-                                energy_map
-                                    .0
-                                    .insert(b.0, (settings.energy_per_food, Instant::now()));
-                            }
-                        }
-                    }
+        let (e1, e2) = match collision_event {
+            CollisionEvent::Started(e1, e2, _) => (*e1, *e2),
+            _ => continue,
+        };
+        let (bullet_entity, (b, owner), other) = match (bullet_query.get(e1), bullet_query.get(e2))
+        {
+            (Ok(b), _) => (e1, b, e2),
+            (_, Ok(b)) => (e2, b, e1),
+            _ => continue,
+        };
+        if spent_bullets.contains(&bullet_entity) {
+            continue;
+        }
+
+        if let Ok(food_transform) = food_query.get(other) {
+            spent_bullets.insert(bullet_entity);
+            commands.entity(other).despawn();
+            commands.entity(bullet_entity).despawn();
+            // Nothing to gain if the owner died since it shot
+            let energy_delta = match cell_query.get_mut(owner.0) {
+                Ok((_, _, _, (mut energy, mut ledger))) => {
+                    let gained = energy.add(settings.energy_per_food, config.max_energy);
+                    ledger.record(EnergyReason::Food, gained);
+                    gained
                 }
+                Err(_) => 0.0,
+            };
+            food_writer.send(FoodEaten {
+                cell: b.0,
+                pos: food_transform.translation.truncate(),
+                energy_delta,
+            });
+            continue;
+        }
+        if wall_query.contains(other) {
+            spent_bullets.insert(bullet_entity);
+            commands.entity(bullet_entity).despawn();
+            continue;
+        }
+
+        let Ok((victim, victim_lineage, victim_traits, _)) = cell_query.get(other) else {
+            continue;
+        };
+        if !settings.is_combat_enabled || victim.0 == b.0 {
+            continue;
+        }
+        let victim_lineage = victim_lineage.0;
+        if !settings.is_friendly_fire_enabled {
+            let is_friendly = cell_query
+                .get(owner.0)
+                .is_ok_and(|(_, lineage, _, _)| lineage.0 == victim_lineage);
+            if is_friendly {
+                continue;
             }
-            _ => {}
+        }
+
+        spent_bullets.insert(bullet_entity);
+        commands.entity(bullet_entity).despawn();
+        let damage = victim_traits.damage_taken(settings.bullet_damage, &config);
+        let Ok((_, _, _, (mut victim_energy, mut victim_ledger))) = cell_query.get_mut(other)
+        else {
+            continue;
+        };
+        // Already dead, the cell is despawned by the kill rules
+        if victim_energy.0 <= 0.0 {
+            continue;
+        }
+        victim_energy.0 -= damage;
+        victim_ledger.record(EnergyReason::Combat, -damage);
+
+        let mut reward = damage;
+        if victim_energy.0 <= 0.0 {
+            reward += settings.kill_bounty;
+            commands.entity(other).insert(KilledInCombat);
+        }
+        if let Ok((_, _, _, (mut energy, mut ledger))) = cell_query.get_mut(owner.0) {
+            let gained = energy.add(reward, config.max_energy);
+            ledger.record(EnergyReason::Combat, gained);
+        }
+    }
+}
+
+/// Keeps cells and bullets on the collision masks of the current combat setting
+fn update_collision_groups(
+    settings: Res<DynamicSettings>,
+    mut cell_query: Query<&mut CollisionGroups, (With<Cell>, Without<Bullet>)>,
+    mut bullet_query: Query<&mut CollisionGroups, (With<Bullet>, Without<Cell>)>,
+) {
+    let (cell_mask, bullet_mask) = if settings.is_combat_enabled {
+        (MASK_CELLS_COMBAT, MASK_BULLET_COMBAT)
+    } else {
+        (MASK_CELLS, MASK_BULLET)
+    };
+    let cell_mask = Group::from_bits_truncate(cell_mask);
+    let bullet_mask = Group::from_bits_truncate(bullet_mask);
+
+    // Only touch the ones that differ so rapier isn't asked to update every collider
+    for mut groups in cell_query.iter_mut() {
+        if groups.filters != cell_mask {
+            groups.filters = cell_mask;
+        }
+    }
+    for mut groups in bullet_query.iter_mut() {
+        if groups.filters != bullet_mask {
+            groups.filters = bullet_mask;
         }
     }
 }
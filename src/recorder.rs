@@ -0,0 +1,261 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    brain::BrainModel,
+    bullet::Bullet,
+    cell::{
+        energy::{Energy, EnergyLedger, EnergyReason, PopulationLedger},
+        user::UserControlledCell,
+        Brain,
+    },
+    food::Food,
+    sim_config::SimConfig,
+    stats::{DeathCause, Demographics},
+    trackers::{BirthTs, FitnessScores, SimClock, SimSet},
+};
+
+/// Appends a row of population stats to `SimConfig::stats_file` every `stats_interval`
+pub struct StatsRecorderPlugin;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RowFormat {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Resource)]
+struct StatsRecorder {
+    writer: BufWriter<File>,
+    format: RowFormat,
+    /// Totals at the previous row, rows only count what happened since then
+    last_demographics: Demographics,
+    last_ledger: EnergyLedger,
+}
+
+/// Births, deaths and energy changes are counted since the previous row,
+/// the energy changes are the population-wide net per reason
+#[derive(Serialize)]
+struct StatsRow {
+    time: f32,
+    population: usize,
+    food: usize,
+    bullets: usize,
+    births: u64,
+    deaths_starved: u64,
+    deaths_killed: u64,
+    deaths_idle: u64,
+    deaths_revolving: u64,
+    deaths_one_direction: u64,
+    deaths_no_bullets: u64,
+    deaths_culled: u64,
+    energy_food: f32,
+    energy_decay: f32,
+    energy_miss_penalty: f32,
+    energy_idle_penalty: f32,
+    energy_combat: f32,
+    energy_reproduction: f32,
+    mean_energy: f32,
+    median_energy: f32,
+    max_energy: f32,
+    mean_fitness: f32,
+    max_age: f32,
+    mean_weight: f64,
+}
+
+impl Plugin for StatsRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup).add_systems(
+            FixedUpdate,
+            record_stats
+                .run_if(resource_exists::<StatsRecorder>())
+                .in_set(SimSet::PostPhysics),
+        );
+    }
+}
+
+fn setup(mut commands: Commands, config: Res<SimConfig>) {
+    let Some(path) = &config.stats_file else {
+        return;
+    };
+
+    match StatsRecorder::create(path) {
+        Ok(recorder) => {
+            info!("Recording stats to {}", path);
+            commands.insert_resource(recorder);
+        }
+        Err(e) => error!("Failed to create stats file {}: {}", path, e),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_stats(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    config: Res<SimConfig>,
+    mut recorder: ResMut<StatsRecorder>,
+    demographics: Res<Demographics>,
+    population_ledger: Res<PopulationLedger>,
+    cell_query: Query<
+        (&Energy, &EnergyLedger, &BirthTs, &FitnessScores, &Brain),
+        Without<UserControlledCell>,
+    >,
+    food_query: Query<With<Food>>,
+    bullet_query: Query<With<Bullet>>,
+) {
+    // Rows line up with the clock so that they keep their spacing across snapshot loads
+    if !clock
+        .ticks()
+        .is_multiple_of(SimClock::ticks_for(config.stats_interval))
+    {
+        return;
+    }
+
+    let mut energies = Vec::new();
+    let mut total_fitness = 0.0;
+    let mut total_weight = 0.0;
+    let mut max_age: f32 = 0.0;
+    for (energy, _, birth_ts, fitness_scores, brain) in cell_query.iter() {
+        energies.push(energy.0);
+        total_fitness += fitness_scores.get_fitness();
+        total_weight += brain.0.mean_abs_weight();
+        max_age = max_age.max(birth_ts.0.elapsed(&clock));
+    }
+    energies.sort_by(|a, b| a.total_cmp(b));
+    let population = cell_query.iter().len();
+
+    let last = &recorder.last_demographics;
+    let deaths = |cause| demographics.deaths(cause) - last.deaths(cause);
+    let ledger = population_ledger.total(cell_query.iter().map(|(_, ledger, ..)| ledger));
+    let energy = ledger.since(&recorder.last_ledger);
+    let row = StatsRow {
+        time: clock.elapsed(),
+        population,
+        food: food_query.iter().len(),
+        bullets: bullet_query.iter().len(),
+        births: demographics.births - last.births,
+        deaths_starved: deaths(DeathCause::Starved),
+        deaths_killed: deaths(DeathCause::Killed),
+        deaths_idle: deaths(DeathCause::Idle),
+        deaths_revolving: deaths(DeathCause::Revolving),
+        deaths_one_direction: deaths(DeathCause::OneDirection),
+        deaths_no_bullets: deaths(DeathCause::NoBullets),
+        deaths_culled: deaths(DeathCause::Culled),
+        energy_food: energy.net(EnergyReason::Food),
+        energy_decay: energy.net(EnergyReason::Decay),
+        energy_miss_penalty: energy.net(EnergyReason::MissPenalty),
+        energy_idle_penalty: energy.net(EnergyReason::IdlePenalty),
+        energy_combat: energy.net(EnergyReason::Combat),
+        energy_reproduction: energy.net(EnergyReason::Reproduction),
+        mean_energy: mean(energies.iter().sum(), energies.len()),
+        median_energy: median(&energies),
+        max_energy: energies.last().copied().unwrap_or(0.0),
+        mean_fitness: mean(total_fitness, population),
+        max_age,
+        mean_weight: total_weight / population.max(1) as f64,
+    };
+    recorder.last_demographics = demographics.clone();
+    recorder.last_ledger = ledger;
+
+    if let Err(e) = recorder.write_row(&row) {
+        error!("Failed to record stats, recording stopped: {}", e);
+        commands.remove_resource::<StatsRecorder>();
+    }
+}
+
+impl StatsRecorder {
+    fn create(path: &str) -> io::Result<Self> {
+        let format = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("jsonl") | Some("json") => RowFormat::JsonLines,
+            _ => RowFormat::Csv,
+        };
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        if format == RowFormat::Csv {
+            writeln!(writer, "{}", StatsRow::CSV_HEADER)?;
+        }
+
+        Ok(Self {
+            writer,
+            format,
+            last_demographics: Demographics::default(),
+            last_ledger: EnergyLedger::default(),
+        })
+    }
+
+    /// Flushed right away so that the file can be followed while the sim runs
+    fn write_row(&mut self, row: &StatsRow) -> io::Result<()> {
+        match self.format {
+            RowFormat::Csv => writeln!(self.writer, "{}", row.to_csv())?,
+            RowFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, row)?;
+                writeln!(self.writer)?;
+            }
+        }
+        self.writer.flush()
+    }
+}
+
+impl StatsRow {
+    const CSV_HEADER: &'static str = "time,population,food,bullets,births,\
+        deaths_starved,deaths_killed,deaths_idle,deaths_revolving,deaths_one_direction,\
+        deaths_no_bullets,deaths_culled,energy_food,energy_decay,energy_miss_penalty,\
+        energy_idle_penalty,energy_combat,energy_reproduction,mean_energy,median_energy,max_energy,\
+        mean_fitness,max_age,mean_weight";
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{:.2},{},{},{},{},{},{},{},{},{},{},{},{:.1},{:.1},{:.1},{:.1},{:.1},{:.1},{:.3},{:.3},{:.3},{:.4},{:.2},{:.4}",
+            self.time,
+            self.population,
+            self.food,
+            self.bullets,
+            self.births,
+            self.deaths_starved,
+            self.deaths_killed,
+            self.deaths_idle,
+            self.deaths_revolving,
+            self.deaths_one_direction,
+            self.deaths_no_bullets,
+            self.deaths_culled,
+            self.energy_food,
+            self.energy_decay,
+            self.energy_miss_penalty,
+            self.energy_idle_penalty,
+            self.energy_combat,
+            self.energy_reproduction,
+            self.mean_energy,
+            self.median_energy,
+            self.max_energy,
+            self.mean_fitness,
+            self.max_age,
+            self.mean_weight,
+        )
+    }
+}
+
+fn mean(total: f32, count: usize) -> f32 {
+    if count == 0 {
+        return 0.0;
+    }
+    total / count as f32
+}
+
+/// `values` must be sorted
+fn median(values: &[f32]) -> f32 {
+    let n = values.len();
+    match n {
+        0 => 0.0,
+        _ if n % 2 == 1 => values[n / 2],
+        _ => (values[n / 2 - 1] + values[n / 2]) / 2.0,
+    }
+}
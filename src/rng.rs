@@ -0,0 +1,36 @@
+use bevy::{
+    ecs::schedule::{ExecutorKind, ScheduleLabel},
+    prelude::*,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+pub struct RngPlugin {
+    /// A random seed is picked when unset
+    pub seed: Option<u64>,
+}
+
+/// The only source of randomness for the simulation, runs with the same seed
+/// produce the same population after the same number of ticks
+#[derive(Resource)]
+pub struct SimRng(pub ChaCha8Rng);
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        info!("Simulation seed: {}", seed);
+
+        app.insert_resource(SimRng(ChaCha8Rng::seed_from_u64(seed)));
+
+        // Systems that touch the rng or spawn entities have no fixed order
+        // under the multi threaded executor
+        single_threaded(app, Startup);
+        single_threaded(app, FixedUpdate);
+    }
+}
+
+fn single_threaded(app: &mut App, label: impl ScheduleLabel) {
+    app.edit_schedule(label, |schedule| {
+        schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+    });
+}
@@ -1,6 +1,13 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::*;
+use crate::{
+    cell::kill_rules::KillRulesConfig,
+    sim_config::SimConfig,
+    snapshot::{LoadSnapshotEvent, SaveSnapshotEvent},
+    trackers::TimeControl,
+    *,
+};
 
 pub struct SettingsPlugin;
 
@@ -13,12 +20,20 @@ pub struct SimSettings {
     pub follow_focused_cell: bool,
 }
 
-#[derive(Resource)]
+#[derive(Resource, Clone, Serialize, Deserialize)]
 pub struct DynamicSettings {
     pub bullet_miss_penalty: f32,
     pub energy_per_food: f32,
     pub energy_decay_rate: f32,
+    /// Fitness function scoring the cells, can be swapped mid-run
+    pub fitness: String,
     pub num_food: usize,
+    pub is_combat_enabled: bool,
+    pub bullet_damage: f32,
+    pub kill_bounty: f32,
+    /// Whether cells of the same lineage can damage each other
+    pub is_friendly_fire_enabled: bool,
+    pub kill_rules: KillRulesConfig,
 }
 
 impl Default for SimSettings {
@@ -36,12 +51,22 @@ impl Default for SimSettings {
 impl Plugin for SettingsPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(SimSettings::default())
-            .insert_resource(DynamicSettings::new())
-            .add_systems(Update, handle_keyboard_input);
+            .init_resource::<SimConfig>()
+            .init_resource::<DynamicSettings>()
+            .add_systems(
+                Update,
+                handle_keyboard_input.run_if(resource_exists::<Input<KeyCode>>()),
+            );
     }
 }
 
-fn handle_keyboard_input(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<SimSettings>) {
+fn handle_keyboard_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<SimSettings>,
+    mut time_control: ResMut<TimeControl>,
+    mut save_writer: EventWriter<SaveSnapshotEvent>,
+    mut load_writer: EventWriter<LoadSnapshotEvent>,
+) {
     if keyboard_input.just_pressed(KeyCode::Tab) {
         settings.show_side_panel = !settings.show_side_panel;
     }
@@ -57,15 +82,45 @@ fn handle_keyboard_input(keyboard_input: Res<Input<KeyCode>>, mut settings: ResM
     if keyboard_input.just_pressed(KeyCode::C) {
         settings.follow_focused_cell = !settings.follow_focused_cell;
     }
+    if keyboard_input.just_pressed(KeyCode::K) {
+        time_control.toggle_pause();
+    }
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        time_control.step();
+    }
+    if keyboard_input.just_pressed(KeyCode::J) {
+        time_control.slow_down();
+    }
+    if keyboard_input.just_pressed(KeyCode::L) {
+        time_control.speed_up();
+    }
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        save_writer.send(SaveSnapshotEvent(quicksave_path()));
+    }
+    if keyboard_input.just_pressed(KeyCode::F9) {
+        load_writer.send(LoadSnapshotEvent(quicksave_path()));
+    }
+}
+
+fn quicksave_path() -> String {
+    format!("{}/{}", SNAPSHOT_DIR, QUICKSAVE_FILE)
 }
 
-impl DynamicSettings {
-    fn new() -> Self {
+/// Starts off from the `SimConfig` of the run
+impl FromWorld for DynamicSettings {
+    fn from_world(world: &mut World) -> Self {
+        let config = world.resource::<SimConfig>();
         Self {
-            bullet_miss_penalty: BULLET_MISS_PENALTY,
-            energy_per_food: ENERGY_PER_FOOD,
-            num_food: NUM_FOOD,
-            energy_decay_rate: ENERGY_DECAY_RATE,
+            bullet_miss_penalty: config.bullet_miss_penalty,
+            energy_per_food: config.energy_per_food,
+            num_food: config.num_food,
+            energy_decay_rate: config.energy_decay_rate,
+            fitness: config.fitness.clone(),
+            is_combat_enabled: config.is_combat_enabled,
+            bullet_damage: config.bullet_damage,
+            kill_bounty: config.kill_bounty,
+            is_friendly_fire_enabled: config.is_friendly_fire_enabled,
+            kill_rules: config.kill_rules.clone(),
         }
     }
 }
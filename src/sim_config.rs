@@ -0,0 +1,520 @@
+use std::{fmt, fs, io, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boundary::BoundaryMode,
+    brain::{BrainKind, MutationParams},
+    cell::{kill_rules::KillRulesConfig, MateSelection},
+    nn::{Activation, Crossover},
+    *,
+};
+
+/// Tuning knobs of a run, loaded from a RON file and the command line.
+/// Fields left out of the file keep the defaults from `configs.rs`
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimConfig {
+    /// Fixed rng seed, a random one is picked when None
+    pub seed: Option<u64>,
+
+    // Environment
+    pub world_width: usize,
+    pub world_height: usize,
+    pub boundary_mode: BoundaryMode,
+
+    // Cell
+    pub num_cells: usize,
+    pub cell_speed: f32,
+    pub cell_size: f32,
+    pub base_energy: f32,
+    pub max_energy: f32,
+    pub energy_decay_rate: f32,
+    /// Taken from the parent on every replication
+    pub reproduction_cost: f32,
+    /// Name of a registered fitness function, scales the energy decay
+    pub fitness: String,
+    pub update_interval: f32,
+    pub vision_radius: f32,
+
+    // Body traits
+    pub trait_mutation_rate: f32,
+    pub trait_mutation_variation: f32,
+    pub trait_energy_cost: f32,
+
+    // Bullet
+    pub bullet_lifespan: f32,
+    pub bullet_speed: f32,
+    pub bullet_fire_rate: f32,
+    pub bullet_miss_penalty: f32,
+
+    /// Heuristics culling the cells that don't go anywhere or never shoot
+    pub kill_rules: KillRulesConfig,
+
+    // Combat
+    pub is_combat_enabled: bool,
+    pub bullet_damage: f32,
+    pub kill_bounty: f32,
+    pub is_friendly_fire_enabled: bool,
+
+    // Food
+    pub num_food: usize,
+    pub energy_per_food: f32,
+
+    // Sensors
+    pub is_cell_sensor_enabled: bool,
+    pub is_bullet_sensor_enabled: bool,
+    pub is_energy_sensor_enabled: bool,
+    /// Distance to the nearest wall, always far away without walls or soft edges
+    pub is_wall_sensor_enabled: bool,
+    pub is_vision_enabled: bool,
+    pub num_vision_rays: usize,
+    pub vision_fov_degrees: f32,
+
+    // NN
+    pub brain_kind: BrainKind,
+    /// Only used by dense brains
+    pub hidden_layers: Vec<usize>,
+    /// One per hidden layer, or a single one shared by all of them
+    pub hidden_activations: Vec<Activation>,
+    pub output_activation: Activation,
+    pub activation_mutation_rate: f32,
+    /// Recurrent outputs, each one is fed back as an input on the next update
+    pub num_memory_neurons: usize,
+    pub brain_mutation_rate: f32,
+    pub brain_mutation_variation: f32,
+    pub is_crossover_enabled: bool,
+    pub mate_selection: MateSelection,
+    pub crossover: Crossover,
+    pub neat_add_node_rate: f32,
+    pub neat_add_connection_rate: f32,
+    pub neat_compatibility_threshold: f64,
+
+    // Stats recording
+    /// Csv file, or json lines when the extension is `.jsonl` or `.json`
+    pub stats_file: Option<String>,
+    pub stats_interval: f32,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Override(String),
+    Invalid(String),
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            world_width: W,
+            world_height: H,
+            boundary_mode: BoundaryMode::None,
+            num_cells: NUM_CELLS,
+            cell_speed: CELL_SPEED,
+            cell_size: CELL_SIZE,
+            base_energy: BASE_ENERGY,
+            max_energy: MAX_ENERGY,
+            energy_decay_rate: ENERGY_DECAY_RATE,
+            reproduction_cost: REPRODUCTION_COST,
+            fitness: DEFAULT_FITNESS.to_string(),
+            update_interval: UPDATE_INTERVAL,
+            vision_radius: VISION_RADIUS,
+            trait_mutation_rate: TRAIT_MUTATION_RATE,
+            trait_mutation_variation: TRAIT_MUTATION_VARIATION,
+            trait_energy_cost: TRAIT_ENERGY_COST,
+            bullet_lifespan: BULLET_LIFESPAN,
+            bullet_speed: BULLET_SPEED,
+            bullet_fire_rate: BULLET_FIRE_RATE,
+            bullet_miss_penalty: BULLET_MISS_PENALTY,
+            kill_rules: KillRulesConfig::default(),
+            is_combat_enabled: IS_COMBAT_ENABLED,
+            bullet_damage: BULLET_DAMAGE,
+            kill_bounty: KILL_BOUNTY,
+            is_friendly_fire_enabled: IS_FRIENDLY_FIRE_ENABLED,
+            num_food: NUM_FOOD,
+            energy_per_food: ENERGY_PER_FOOD,
+            is_cell_sensor_enabled: IS_CELL_SENSOR_ENABLED,
+            is_bullet_sensor_enabled: IS_BULLET_SENSOR_ENABLED,
+            is_energy_sensor_enabled: IS_ENERGY_SENSOR_ENABLED,
+            is_wall_sensor_enabled: IS_WALL_SENSOR_ENABLED,
+            is_vision_enabled: IS_VISION_ENABLED,
+            num_vision_rays: NUM_VISION_RAYS,
+            vision_fov_degrees: VISION_FOV_DEGREES,
+            brain_kind: BrainKind::Dense,
+            hidden_layers: vec![NUM_HIDDEN_NODES],
+            hidden_activations: vec![Activation::Sigmoid],
+            output_activation: Activation::Sigmoid,
+            activation_mutation_rate: ACTIVATION_MUTATION_RATE,
+            num_memory_neurons: NUM_MEMORY_NEURONS,
+            brain_mutation_rate: BRAIN_MUTATION_RATE,
+            brain_mutation_variation: BRAIN_MUTATION_VARIATION,
+            is_crossover_enabled: IS_CROSSOVER_ENABLED,
+            mate_selection: MateSelection::Nearest,
+            crossover: Crossover::Uniform,
+            neat_add_node_rate: NEAT_ADD_NODE_RATE,
+            neat_add_connection_rate: NEAT_ADD_CONNECTION_RATE,
+            neat_compatibility_threshold: NEAT_COMPATIBILITY_THRESHOLD,
+            stats_file: None,
+            stats_interval: STATS_RECORD_INTERVAL_SECS,
+        }
+    }
+}
+
+impl SimConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::from_str(&contents)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        fs::write(path, self.to_ron())?;
+        Ok(())
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("config is always serializable")
+    }
+
+    /// Overrides a single field from a `key=value` pair,
+    /// the value is parsed the same way as in the config file.
+    /// Nested fields are reached with dots, as in `kill_rules.idle.is_enabled=false`
+    pub fn set(&mut self, pair: &str) -> Result<(), ConfigError> {
+        let Some((key, value)) = pair.split_once('=') else {
+            return Err(ConfigError::Override(format!(
+                "expected key=value, found {}",
+                pair
+            )));
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let value: serde_json::Value = ron::from_str(&quote_variants(value))
+            .map_err(|e| ConfigError::Override(format!("bad value for {}: {}", key, e)))?;
+
+        // Round trips through a generic value so that any field can be set by name
+        let mut fields = serde_json::to_value(&*self).expect("config is always serializable");
+        let mut field = &mut fields;
+        for name in key.split('.') {
+            field = match field {
+                serde_json::Value::Object(nested) if nested.contains_key(name) => {
+                    nested.get_mut(name).unwrap()
+                }
+                _ => return Err(ConfigError::Override(format!("unknown config key {}", key))),
+            };
+        }
+        *field = value;
+
+        *self = serde_json::from_value(fields)
+            .map_err(|e| ConfigError::Override(format!("{} in {}", e, pair)))?;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+        let mut check = |is_valid: bool, msg: &str| {
+            if !is_valid {
+                errors.push(msg.to_string());
+            }
+        };
+
+        check(
+            self.world_width > 0 && self.world_height > 0,
+            "world_width and world_height must be positive",
+        );
+        check(self.num_cells > 0, "num_cells must be positive");
+        check(self.cell_speed >= 0.0, "cell_speed can't be negative");
+        check(self.base_energy > 0.0, "base_energy must be positive");
+        check(
+            self.max_energy >= self.base_energy,
+            "max_energy can't be less than base_energy",
+        );
+        check(
+            self.energy_decay_rate >= 0.0,
+            "energy_decay_rate can't be negative",
+        );
+        check(
+            self.reproduction_cost >= 0.0,
+            "reproduction_cost can't be negative",
+        );
+        check(
+            self.update_interval > 0.0,
+            "update_interval must be positive",
+        );
+        check(self.vision_radius > 0.0, "vision_radius must be positive");
+        check(self.cell_size > 0.0, "cell_size must be positive");
+        check(
+            (0.0..=1.0).contains(&self.trait_mutation_rate),
+            "trait_mutation_rate must be within 0..=1",
+        );
+        check(
+            self.trait_mutation_variation > 0.0 && self.trait_mutation_variation < 1.0,
+            "trait_mutation_variation must be within 0..1",
+        );
+        check(
+            self.trait_energy_cost >= 0.0,
+            "trait_energy_cost can't be negative",
+        );
+        check(
+            self.bullet_lifespan > 0.0,
+            "bullet_lifespan must be positive",
+        );
+        check(self.bullet_speed > 0.0, "bullet_speed must be positive");
+        check(
+            self.bullet_fire_rate >= 0.0,
+            "bullet_fire_rate can't be negative",
+        );
+        check(self.bullet_damage >= 0.0, "bullet_damage can't be negative");
+        check(self.kill_bounty >= 0.0, "kill_bounty can't be negative");
+        check(
+            self.kill_rules.no_bullets.penalty >= 0.0,
+            "kill_rules.no_bullets.penalty can't be negative",
+        );
+        check(
+            self.energy_per_food >= 0.0,
+            "energy_per_food can't be negative",
+        );
+        check(
+            !self.is_vision_enabled || self.num_vision_rays > 0,
+            "num_vision_rays must be positive when vision is enabled",
+        );
+        check(
+            self.vision_fov_degrees > 0.0 && self.vision_fov_degrees <= 360.0,
+            "vision_fov_degrees must be within 0..=360",
+        );
+        check(
+            !self.hidden_layers.is_empty() && self.hidden_layers.iter().all(|s| *s > 0),
+            "hidden_layers needs at least one layer and no empty layers",
+        );
+        check(
+            self.hidden_activations.len() == 1
+                || self.hidden_activations.len() == self.hidden_layers.len(),
+            "hidden_activations needs a single entry or one per hidden layer",
+        );
+        check(
+            (0.0..=1.0).contains(&self.activation_mutation_rate),
+            "activation_mutation_rate must be within 0..=1",
+        );
+        check(
+            self.brain_kind == BrainKind::Dense
+                || (self.hidden_activations.iter().all(|a| *a == Activation::Sigmoid)
+                    && self.output_activation == Activation::Sigmoid
+                    && self.activation_mutation_rate == 0.0),
+            "hidden_activations, output_activation and activation_mutation_rate only apply to Dense brains, NEAT nodes are always Sigmoid",
+        );
+        check(
+            (0.0..=1.0).contains(&self.brain_mutation_rate),
+            "brain_mutation_rate must be within 0..=1",
+        );
+        check(
+            self.brain_mutation_variation > 0.0,
+            "brain_mutation_variation must be positive",
+        );
+        check(
+            (0.0..=1.0).contains(&self.neat_add_node_rate)
+                && (0.0..=1.0).contains(&self.neat_add_connection_rate),
+            "neat_add_node_rate and neat_add_connection_rate must be within 0..=1",
+        );
+        check(
+            self.neat_compatibility_threshold > 0.0,
+            "neat_compatibility_threshold must be positive",
+        );
+        check(self.stats_interval > 0.0, "stats_interval must be positive");
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors.join(", ")))
+        }
+    }
+
+    /// Brain inputs for the enabled sensors, followed by the memory
+    pub fn num_inputs(&self) -> usize {
+        let mut num_inputs = NUM_BASE_INPUTS + self.num_memory_neurons;
+        if self.is_cell_sensor_enabled {
+            num_inputs += 3;
+        }
+        if self.is_bullet_sensor_enabled {
+            num_inputs += 3;
+        }
+        if self.is_energy_sensor_enabled {
+            num_inputs += 1;
+        }
+        if self.is_vision_enabled {
+            num_inputs += self.num_vision_rays * 2;
+        }
+        if self.is_wall_sensor_enabled {
+            num_inputs += 1;
+        }
+
+        num_inputs
+    }
+
+    /// Actions, followed by the memory
+    pub fn num_outputs(&self) -> usize {
+        NUM_OUTPUT_NODES + self.num_memory_neurons
+    }
+
+    pub fn net_arch(&self) -> Vec<usize> {
+        let mut arch = vec![self.num_inputs()];
+        arch.extend(self.hidden_layers.iter());
+        arch.push(self.num_outputs());
+        arch
+    }
+
+    /// Activation of every dense layer after the input layer
+    pub fn layer_activations(&self) -> Vec<Activation> {
+        let mut activations: Vec<Activation> = match self.hidden_activations.as_slice() {
+            [shared] => vec![*shared; self.hidden_layers.len()],
+            per_layer => per_layer.to_vec(),
+        };
+        activations.push(self.output_activation);
+        activations
+    }
+
+    pub fn mutation_params(&self) -> MutationParams {
+        MutationParams {
+            rate: self.brain_mutation_rate,
+            variation: self.brain_mutation_variation,
+            activation_rate: self.activation_mutation_rate,
+            add_node_rate: self.neat_add_node_rate,
+            add_connection_rate: self.neat_add_connection_rate,
+        }
+    }
+}
+
+/// Enum variants would come out of ron as unit values while serde_json reads them
+/// from strings, so bare capitalized identifiers other than `None` and `Some` get quoted
+fn quote_variants(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars().peekable();
+    let mut in_string = false;
+    let mut prev = ' ';
+    while let Some(c) = chars.next() {
+        if in_string || c == '"' {
+            if c == '"' && prev != '\\' {
+                in_string = !in_string;
+            }
+            out.push(c);
+            prev = c;
+            continue;
+        }
+        if !c.is_ascii_uppercase() || prev.is_ascii_alphanumeric() || prev == '_' {
+            out.push(c);
+            prev = c;
+            continue;
+        }
+
+        let mut ident = c.to_string();
+        while let Some(&next) = chars.peek() {
+            if !next.is_ascii_alphanumeric() && next != '_' {
+                break;
+            }
+            ident.push(next);
+            chars.next();
+        }
+        match ident.as_str() {
+            "None" | "Some" => out.push_str(&ident),
+            _ => out.push_str(&format!("{:?}", ident)),
+        }
+        prev = 'a';
+    }
+
+    out
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "io error: {}", e),
+            ConfigError::Parse(e) => write!(f, "parse error: {}", e),
+            ConfigError::Override(e) => write!(f, "bad override: {}", e),
+            ConfigError::Invalid(e) => write!(f, "invalid config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for ConfigError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_variants_are_quoted() {
+        assert_eq!(quote_variants("Tanh"), "\"Tanh\"");
+        assert_eq!(quote_variants("LeakyRelu"), "\"LeakyRelu\"");
+        assert_eq!(quote_variants("[Relu, Tanh]"), "[\"Relu\", \"Tanh\"]");
+        assert_eq!(quote_variants("[Relu,Tanh]"), "[\"Relu\",\"Tanh\"]");
+    }
+
+    #[test]
+    fn options_are_left_alone() {
+        assert_eq!(quote_variants("Some(Sigmoid)"), "Some(\"Sigmoid\")");
+        assert_eq!(quote_variants("None"), "None");
+        assert_eq!(quote_variants("Some(3)"), "Some(3)");
+    }
+
+    #[test]
+    fn strings_are_left_alone() {
+        for value in [
+            r#""Out/Stats.csv""#,
+            r#""say \"Hi\" Now""#,
+            r#"Some("A B")"#,
+            r#"["X", "y"]"#,
+        ] {
+            assert_eq!(quote_variants(value), value);
+        }
+        assert_eq!(quote_variants(r#"("\"Q\"", Tanh)"#), r#"("\"Q\"", "Tanh")"#);
+    }
+
+    #[test]
+    fn numbers_and_lowercase_are_left_alone() {
+        for value in [
+            "1E5",
+            "2.5E-3",
+            "-1e5",
+            "42",
+            "true",
+            "false",
+            "[6, 4]",
+            "cell_Size",
+        ] {
+            assert_eq!(quote_variants(value), value);
+        }
+    }
+
+    #[test]
+    fn quoted_overrides_parse() {
+        let mut config = SimConfig::default();
+        config.set("output_activation=Tanh").unwrap();
+        config.set("hidden_activations=[Relu, Tanh]").unwrap();
+        config.set("stats_file=Some(\"Out/Stats.csv\")").unwrap();
+        config.set("energy_per_food=1E1").unwrap();
+        config.set("kill_rules.idle.is_enabled=false").unwrap();
+        assert_eq!(config.output_activation, Activation::Tanh);
+        assert_eq!(
+            config.hidden_activations,
+            vec![Activation::Relu, Activation::Tanh]
+        );
+        assert_eq!(config.stats_file.as_deref(), Some("Out/Stats.csv"));
+        assert_eq!(config.energy_per_food, 10.0);
+        assert!(!config.kill_rules.idle.is_enabled);
+
+        config.set("stats_file=None").unwrap();
+        assert_eq!(config.stats_file, None);
+    }
+}
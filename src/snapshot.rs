@@ -0,0 +1,495 @@
+use std::{fmt, fs, io, path::Path, time::Duration};
+
+use bevy::{prelude::*, time::common_conditions::on_timer, utils::HashMap};
+use bevy_rapier2d::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    brain::{BrainKind, BrainNet},
+    bullet::{Bullet, BulletBundle},
+    cell::{
+        bundle::CellBundle,
+        energy::{Energy, EnergyLedger, PopulationLedger},
+        genealogy::{Ancestry, Genealogy},
+        traits::BodyTraits,
+        user::UserControlledCell,
+        Brain, Cell, CellId, Lineage, Memory,
+    },
+    food::{Food, FoodBundle},
+    gui::GraphPoints,
+    neat::{Innovations, Species, SpeciesRegistry},
+    nn::NetError,
+    rng::SimRng,
+    settings::DynamicSettings,
+    sim_config::SimConfig,
+    stats::SimStats,
+    trackers::{
+        BirthPlace, BirthTs, FitnessScores, LastBulletFired, LastUpdated, NumCellsSpawned,
+        OneSecondTimer, PeriodicUpdateInterval, SimClock, SimTracker,
+    },
+    *,
+};
+
+/// Version of the on-disk snapshot format
+pub const SNAPSHOT_VERSION: u32 = 13;
+
+type SavedCell<'a> = (
+    &'a Cell,
+    &'a Lineage,
+    &'a Ancestry,
+    &'a Transform,
+    &'a Velocity,
+    &'a ExternalForce,
+    (
+        &'a Brain,
+        &'a Memory,
+        &'a BodyTraits,
+        (&'a Energy, &'a EnergyLedger),
+    ),
+    Option<&'a Species>,
+    &'a BirthPlace,
+    &'a BirthTs,
+    &'a LastUpdated,
+    &'a LastBulletFired,
+    &'a PeriodicUpdateInterval,
+    &'a FitnessScores,
+    &'a NumCellsSpawned,
+);
+/// Everything a snapshot replaces, the user cell stays as it is
+type SnapshotEntity = Or<(
+    With<Food>,
+    With<Bullet>,
+    (With<Cell>, Without<UserControlledCell>),
+)>;
+
+pub struct SnapshotPlugin {
+    /// Snapshot to restore once the world has been set up
+    pub resume_from: Option<String>,
+}
+
+#[derive(Event)]
+pub struct SaveSnapshotEvent(pub String);
+#[derive(Event)]
+pub struct LoadSnapshotEvent(pub String);
+
+#[derive(Resource)]
+struct ResumeFrom(Option<String>);
+
+#[derive(Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    version: u32,
+    clock: SimClock,
+    /// The timers of `on_sim_timer` follow the clock, this one is kept separately
+    one_second_timer: SimTracker,
+    rng: ChaCha8Rng,
+    cell_id: u32,
+    settings: DynamicSettings,
+    /// Read by the replication of the tick after the snapshot
+    stats: SimStats,
+    graph_points: Option<GraphPoints>,
+    genealogy: Genealogy,
+    innovations: Innovations,
+    species: SpeciesRegistry,
+    /// Energy booked by the cells that died
+    ledger: EnergyLedger,
+    cells: Vec<CellSnapshot>,
+    food: Vec<Vec2>,
+    bullets: Vec<BulletSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CellSnapshot {
+    id: u32,
+    lineage: u32,
+    parent: Option<u32>,
+    generation: u32,
+    translation: Vec3,
+    rotation: Quat,
+    linvel: Vec2,
+    angvel: f32,
+    force: Vec2,
+    brain: BrainNet,
+    memory: Vec<f64>,
+    traits: BodyTraits,
+    species: Option<u32>,
+    energy: f32,
+    ledger: EnergyLedger,
+    birth_place: Vec2,
+    birth_ts: SimTracker,
+    last_updated: SimTracker,
+    last_bullet_fired: SimTracker,
+    periodic_update_interval: f32,
+    fitness_scores: FitnessScores,
+    num_cells_spawned: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BulletSnapshot {
+    owner: u32,
+    translation: Vec3,
+    linvel: Vec2,
+    birth_ts: SimTracker,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    UnsupportedVersion(u32),
+    /// Brains don't match the configured layer sizes, usually because the enabled sensors changed
+    ArchMismatch {
+        expected: Vec<usize>,
+        found: Vec<usize>,
+    },
+    BrainKindMismatch {
+        expected: BrainKind,
+        found: BrainKind,
+    },
+    /// A brain is malformed, see [`BrainNet::validate`]
+    BadBrain(NetError),
+}
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ResumeFrom(self.resume_from.clone()))
+            .add_event::<SaveSnapshotEvent>()
+            .add_event::<LoadSnapshotEvent>()
+            .add_systems(Startup, resume)
+            .add_systems(Update, save_snapshot)
+            .add_systems(
+                Update,
+                (
+                    load_snapshot,
+                    apply_deferred,
+                    // Restored bodies join the physics world before the next tick, which may
+                    // already despawn some of them
+                    RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::SyncBackend),
+                    apply_deferred,
+                )
+                    .chain()
+                    .run_if(on_event::<LoadSnapshotEvent>()),
+            )
+            .add_systems(
+                Update,
+                autosave.run_if(on_timer(Duration::from_secs_f32(
+                    AUTOSAVE_INTERVAL_MINS * 60.0,
+                ))),
+            );
+    }
+}
+
+fn resume(resume_from: Res<ResumeFrom>, mut writer: EventWriter<LoadSnapshotEvent>) {
+    if let Some(path) = &resume_from.0 {
+        writer.send(LoadSnapshotEvent(path.clone()));
+    }
+}
+
+fn autosave(mut slot: Local<usize>, mut writer: EventWriter<SaveSnapshotEvent>) {
+    writer.send(SaveSnapshotEvent(format!(
+        "{}/autosave-{}.json",
+        SNAPSHOT_DIR, *slot
+    )));
+    *slot = (*slot + 1) % NUM_AUTOSAVE_SLOTS;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_snapshot(
+    mut reader: EventReader<SaveSnapshotEvent>,
+    clock: Res<SimClock>,
+    one_second_timer: Res<OneSecondTimer>,
+    rng: Res<SimRng>,
+    cell_id: Res<CellId>,
+    settings: Res<DynamicSettings>,
+    stats: Res<SimStats>,
+    graph_points: Option<Res<GraphPoints>>,
+    genealogy: Res<Genealogy>,
+    innovations: Res<Innovations>,
+    species_registry: Res<SpeciesRegistry>,
+    population_ledger: Res<PopulationLedger>,
+    cell_query: Query<SavedCell, Without<UserControlledCell>>,
+    food_query: Query<&Transform, With<Food>>,
+    bullet_query: Query<(&Bullet, &Transform, &Velocity, &BirthTs)>,
+) {
+    if reader.is_empty() {
+        return;
+    }
+
+    let mut cells = Vec::new();
+    for (
+        cell,
+        lineage,
+        ancestry,
+        transform,
+        velocity,
+        external_force,
+        (brain, memory, traits, (energy, ledger)),
+        species,
+        birth_place,
+        birth_ts,
+        last_updated,
+        last_bullet_fired,
+        periodic_update_interval,
+        fitness_scores,
+        num_cells_spawned,
+    ) in cell_query.iter()
+    {
+        cells.push(CellSnapshot {
+            id: cell.0,
+            lineage: lineage.0,
+            parent: ancestry.parent,
+            generation: ancestry.generation,
+            translation: transform.translation,
+            rotation: transform.rotation,
+            linvel: velocity.linvel,
+            angvel: velocity.angvel,
+            force: external_force.force,
+            brain: brain.0.clone(),
+            memory: memory.0.clone(),
+            traits: *traits,
+            species: species.map(|s| s.0),
+            energy: energy.0,
+            ledger: *ledger,
+            birth_place: birth_place.0,
+            birth_ts: birth_ts.0,
+            last_updated: last_updated.0,
+            last_bullet_fired: last_bullet_fired.0,
+            periodic_update_interval: periodic_update_interval.0,
+            fitness_scores: fitness_scores.clone(),
+            num_cells_spawned: num_cells_spawned.0,
+        });
+    }
+
+    let snapshot = WorldSnapshot {
+        version: SNAPSHOT_VERSION,
+        clock: *clock,
+        one_second_timer: one_second_timer.0,
+        rng: rng.0.clone(),
+        cell_id: cell_id.0,
+        settings: settings.clone(),
+        stats: stats.clone(),
+        graph_points: graph_points.map(|g| (*g).clone()),
+        genealogy: genealogy.clone(),
+        innovations: innovations.clone(),
+        species: species_registry.clone(),
+        ledger: population_ledger.0,
+        cells,
+        food: food_query
+            .iter()
+            .map(|t| t.translation.truncate())
+            .collect(),
+        bullets: bullet_query
+            .iter()
+            .map(|(b, t, v, birth_ts)| BulletSnapshot {
+                owner: b.0,
+                translation: t.translation,
+                linvel: v.linvel,
+                birth_ts: birth_ts.0,
+            })
+            .collect(),
+    };
+
+    for e in reader.iter() {
+        match snapshot.save(&e.0) {
+            Ok(_) => info!("Saved snapshot to {}", e.0),
+            Err(err) => error!("Failed to save snapshot to {}: {}", e.0, err),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_snapshot(
+    mut commands: Commands,
+    mut reader: EventReader<LoadSnapshotEvent>,
+    config: Res<SimConfig>,
+    mut clock: ResMut<SimClock>,
+    mut one_second_timer: ResMut<OneSecondTimer>,
+    mut rng: ResMut<SimRng>,
+    mut cell_id: ResMut<CellId>,
+    mut settings: ResMut<DynamicSettings>,
+    mut stats: ResMut<SimStats>,
+    graph_points: Option<ResMut<GraphPoints>>,
+    mut genealogy: ResMut<Genealogy>,
+    mut innovations: ResMut<Innovations>,
+    mut species_registry: ResMut<SpeciesRegistry>,
+    mut population_ledger: ResMut<PopulationLedger>,
+    entities_query: Query<Entity, SnapshotEntity>,
+) {
+    let path = match reader.iter().last() {
+        Some(e) => e.0.clone(),
+        None => return,
+    };
+    let snapshot = match WorldSnapshot::load(&path, config.brain_kind, &config.net_arch()) {
+        Ok(v) => v,
+        Err(err) => {
+            error!("Failed to load snapshot from {}: {}", path, err);
+            return;
+        }
+    };
+
+    for entity in entities_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    *clock = snapshot.clock;
+    one_second_timer.0 = snapshot.one_second_timer;
+
+    // Bullets settle their energy through the entity of the cell that shot them
+    let mut owners = HashMap::new();
+
+    for c in snapshot.cells {
+        let mut cell = commands.spawn(CellBundle::new(
+            c.translation.x,
+            c.translation.y,
+            c.id,
+            c.brain,
+            c.traits,
+            &config,
+            &clock,
+            &mut rng.0,
+        ));
+        cell.insert((
+            Transform::from_translation(c.translation)
+                .with_rotation(c.rotation)
+                .with_scale(Vec3::splat(c.traits.sprite_scale(&config))),
+            Velocity {
+                linvel: c.linvel,
+                angvel: c.angvel,
+            },
+            ExternalForce {
+                force: c.force,
+                torque: 0.0,
+            },
+            Lineage(c.lineage),
+            Ancestry {
+                parent: c.parent,
+                generation: c.generation,
+            },
+            BirthPlace(c.birth_place),
+            BirthTs(c.birth_ts),
+            LastUpdated(c.last_updated),
+            LastBulletFired(c.last_bullet_fired),
+            PeriodicUpdateInterval(c.periodic_update_interval),
+            c.fitness_scores,
+            NumCellsSpawned(c.num_cells_spawned),
+            Memory(c.memory),
+            Energy(c.energy),
+            c.ledger,
+        ));
+        owners.insert(c.id, cell.id());
+        if let Some(species) = c.species {
+            cell.insert(Species(species));
+        }
+    }
+    for pos in snapshot.food {
+        commands.spawn(FoodBundle::new(pos.x, pos.y));
+    }
+    for b in snapshot.bullets {
+        // Bullets of dead cells, and of the user controlled one, have no one to pay
+        let owner = owners.get(&b.owner).copied().unwrap_or(Entity::PLACEHOLDER);
+        commands
+            .spawn(BulletBundle::new(
+                b.translation.x,
+                b.translation.y,
+                b.owner,
+                owner,
+                b.linvel,
+                &clock,
+            ))
+            .insert(BirthTs(b.birth_ts));
+    }
+
+    // Restored last, the bundles above draw from the rng
+    rng.0 = snapshot.rng;
+    cell_id.0 = snapshot.cell_id;
+    *settings = snapshot.settings;
+    *stats = snapshot.stats;
+    genealogy.restore(snapshot.genealogy);
+    *innovations = snapshot.innovations;
+    *species_registry = snapshot.species;
+    population_ledger.0 = snapshot.ledger;
+    if let (Some(mut graph_points), Some(saved)) = (graph_points, snapshot.graph_points) {
+        *graph_points = saved;
+    }
+    info!("Resumed from snapshot {}", path);
+}
+
+impl WorldSnapshot {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        let contents = serde_json::to_string(self)?;
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+
+    /// `brain_kind` and `net_arch` are the kind and layer sizes brains are expected to have
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        brain_kind: BrainKind,
+        net_arch: &[usize],
+    ) -> Result<Self, SnapshotError> {
+        let contents = fs::read_to_string(path)?;
+        let snapshot: WorldSnapshot = serde_json::from_str(&contents)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version));
+        }
+        for c in snapshot.cells.iter() {
+            c.brain.validate().map_err(SnapshotError::BadBrain)?;
+            if c.brain.kind() != brain_kind {
+                return Err(SnapshotError::BrainKindMismatch {
+                    expected: brain_kind,
+                    found: c.brain.kind(),
+                });
+            }
+            if !c.brain.fits(net_arch) {
+                return Err(SnapshotError::ArchMismatch {
+                    expected: net_arch.to_vec(),
+                    found: c.brain.layer_sizes(),
+                });
+            }
+        }
+
+        Ok(snapshot)
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "io error: {}", e),
+            SnapshotError::Parse(e) => write!(f, "parse error: {}", e),
+            SnapshotError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported snapshot version {}, expected {}",
+                v, SNAPSHOT_VERSION
+            ),
+            SnapshotError::ArchMismatch { expected, found } => write!(
+                f,
+                "brain layer sizes {:?} don't match the configured {:?}",
+                found, expected
+            ),
+            SnapshotError::BrainKindMismatch { expected, found } => write!(
+                f,
+                "{:?} brains don't match the configured {:?} ones",
+                found, expected
+            ),
+            SnapshotError::BadBrain(e) => write!(f, "bad brain: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Parse(e)
+    }
+}
@@ -0,0 +1,200 @@
+use bevy::{prelude::*, utils::HashMap};
+
+/// Uniform grid of square buckets, updated in place as entities spawn, move and despawn.
+/// Queries visit the buckets in a fixed order, so results don't depend on hashing
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    buckets: HashMap<IVec2, Vec<Entry<T>>>,
+    /// Bucket of every indexed entity
+    locations: HashMap<Entity, IVec2>,
+    /// Bounds of every bucket ever used, searches don't go past them
+    min: IVec2,
+    max: IVec2,
+}
+
+pub struct Entry<T> {
+    pub entity: Entity,
+    pub pos: Vec2,
+    pub item: T,
+}
+
+impl<T> SpatialGrid<T> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            buckets: HashMap::default(),
+            locations: HashMap::default(),
+            min: IVec2::MAX,
+            max: IVec2::MIN,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Adds the entity, or moves it and replaces its item if it's already indexed
+    pub fn insert(&mut self, entity: Entity, pos: Vec2, item: T) {
+        let key = self.key(pos);
+        match self.locations.get(&entity) {
+            Some(old_key) if *old_key == key => {
+                if let Some(entry) = self
+                    .buckets
+                    .get_mut(&key)
+                    .and_then(|b| b.iter_mut().find(|e| e.entity == entity))
+                {
+                    entry.pos = pos;
+                    entry.item = item;
+                }
+                return;
+            }
+            Some(_) => {
+                self.remove(entity);
+            }
+            None => {}
+        }
+
+        self.locations.insert(entity, key);
+        self.buckets
+            .entry(key)
+            .or_default()
+            .push(Entry { entity, pos, item });
+        self.min = self.min.min(key);
+        self.max = self.max.max(key);
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        let Some(key) = self.locations.remove(&entity) else {
+            return false;
+        };
+        if let Some(bucket) = self.buckets.get_mut(&key) {
+            // Keeps the order of the bucket, it decides ties between equally close entries
+            bucket.retain(|e| e.entity != entity);
+            if bucket.is_empty() {
+                self.buckets.remove(&key);
+            }
+        }
+
+        true
+    }
+
+    /// Drops the entities that were despawned without their removal being seen,
+    /// only walks the grid when it holds more entities than `num_alive`
+    pub fn prune(&mut self, num_alive: usize, is_alive: impl Fn(Entity) -> bool) {
+        if self.len() <= num_alive {
+            return;
+        }
+
+        let stale: Vec<Entity> = self
+            .locations
+            .keys()
+            .copied()
+            .filter(|e| !is_alive(*e))
+            .collect();
+        for entity in stale {
+            self.remove(entity);
+        }
+    }
+
+    /// Closest entry within `max_dist` that passes `filter`
+    pub fn nearest(
+        &self,
+        pos: Vec2,
+        max_dist: f32,
+        filter: impl Fn(&Entry<T>) -> bool,
+    ) -> Option<&Entry<T>> {
+        self.k_nearest(pos, 1, max_dist, filter).pop()
+    }
+
+    /// Up to `k` entries within `max_dist` that pass `filter`, closest first
+    pub fn k_nearest(
+        &self,
+        pos: Vec2,
+        k: usize,
+        max_dist: f32,
+        filter: impl Fn(&Entry<T>) -> bool,
+    ) -> Vec<&Entry<T>> {
+        let mut found: Vec<(f32, &Entry<T>)> = Vec::new();
+        if k == 0 || self.is_empty() {
+            return Vec::new();
+        }
+
+        let center = self.key(pos);
+        let max_dist_sq = max_dist * max_dist;
+        let mut ring = 0;
+        loop {
+            for key in ring_keys(center, ring) {
+                let Some(bucket) = self.buckets.get(&key) else {
+                    continue;
+                };
+                for entry in bucket.iter() {
+                    let dist_sq = entry.pos.distance_squared(pos);
+                    if dist_sq > max_dist_sq || !filter(entry) {
+                        continue;
+                    }
+                    // Stable, so ties keep the order in which they were visited
+                    let index = found.partition_point(|(d, _)| *d <= dist_sq);
+                    if index < k {
+                        found.insert(index, (dist_sq, entry));
+                        found.truncate(k);
+                    }
+                }
+            }
+
+            // Anything in the next rings is at least this far away
+            let reach = ring as f32 * self.cell_size;
+            let is_done = found.len() == k && found[k - 1].0 <= reach * reach;
+            let covers_grid =
+                (center - ring).cmple(self.min).all() && (center + ring).cmpge(self.max).all();
+            if is_done || covers_grid || reach > max_dist {
+                break;
+            }
+            ring += 1;
+        }
+
+        found.into_iter().map(|(_, e)| e).collect()
+    }
+
+    /// Every entry within `radius`, bucket by bucket
+    pub fn within_radius(&self, pos: Vec2, radius: f32) -> Vec<&Entry<T>> {
+        let radius_sq = radius * radius;
+        let (from, to) = (self.key(pos - radius), self.key(pos + radius));
+        let mut found = Vec::new();
+        for y in from.y.max(self.min.y)..=to.y.min(self.max.y) {
+            for x in from.x.max(self.min.x)..=to.x.min(self.max.x) {
+                if let Some(bucket) = self.buckets.get(&IVec2::new(x, y)) {
+                    found.extend(
+                        bucket
+                            .iter()
+                            .filter(|e| e.pos.distance_squared(pos) <= radius_sq),
+                    );
+                }
+            }
+        }
+
+        found
+    }
+
+    fn key(&self, pos: Vec2) -> IVec2 {
+        (pos / self.cell_size).floor().as_ivec2()
+    }
+}
+
+/// Keys at exactly `ring` buckets from `center`, row by row
+fn ring_keys(center: IVec2, ring: i32) -> impl Iterator<Item = IVec2> {
+    (-ring..=ring).flat_map(move |dy| {
+        let is_edge_row = dy.abs() == ring;
+        let step = if is_edge_row {
+            1
+        } else {
+            (2 * ring).max(1) as usize
+        };
+        (-ring..=ring)
+            .step_by(step)
+            .map(move |dx| center + IVec2::new(dx, dy))
+    })
+}
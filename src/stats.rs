@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cell::{energy::Energy, user::UserControlledCell},
+    events::CellDied,
+    trackers::{BirthTs, SimClock, SimSet},
+};
+
+pub struct StatsPlugin;
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct SimStats {
+    pub max_score: f32,
+    pub max_age: f32,
+    pub best_cell_pos: Vec2,
+    pub oldest_cell_pos: Vec2,
+}
+
+/// Why a cell was removed from the simulation
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum DeathCause {
+    /// Ran out of energy
+    Starved,
+    /// Lost its last energy to a bullet
+    Killed,
+    /// Stayed around its birth place
+    Idle,
+    /// Kept circling close to its birth place
+    Revolving,
+    /// Only ever moved along one axis
+    OneDirection,
+    /// Lost its last energy to the drain on cells that don't shoot
+    NoBullets,
+    /// Removed by a kill rule registered by a library user
+    Culled,
+}
+
+/// Running totals of births and deaths since the start of the run
+#[derive(Resource, Default, Clone)]
+pub struct Demographics {
+    pub births: u64,
+    deaths: [u64; DeathCause::ALL.len()],
+}
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SimStats::new())
+            .insert_resource(Demographics::default())
+            .add_event::<CellDied>()
+            .add_systems(
+                FixedUpdate,
+                (update_stats, count_deaths).in_set(SimSet::PostPhysics),
+            );
+    }
+}
+
+fn count_deaths(mut demographics: ResMut<Demographics>, mut reader: EventReader<CellDied>) {
+    for e in reader.iter() {
+        demographics.record_death(e.cause);
+    }
+}
+
+fn update_stats(
+    clock: Res<SimClock>,
+    mut stats: ResMut<SimStats>,
+    cells_query: Query<(&Energy, &BirthTs, &Transform), Without<UserControlledCell>>,
+) {
+    let mut max_score = 0.0;
+    let mut max_age = 0.0;
+    let mut best_cell_pos = Vec3::ZERO;
+    let mut oldest_cell_pos = Vec3::ZERO;
+
+    for (energy, birth_ts, transform) in cells_query.iter() {
+        let score = energy.0;
+        if score > max_score {
+            max_score = score;
+            best_cell_pos = transform.translation;
+        }
+
+        let age = birth_ts.0.elapsed(&clock);
+        if age > max_age {
+            max_age = age;
+            oldest_cell_pos = transform.translation;
+        }
+    }
+
+    stats.max_score = max_score;
+    stats.max_age = max_age;
+    stats.best_cell_pos = best_cell_pos.truncate();
+    stats.oldest_cell_pos = oldest_cell_pos.truncate();
+}
+
+impl SimStats {
+    fn new() -> Self {
+        Self {
+            best_cell_pos: Vec2::ZERO,
+            max_age: 0.0,
+            max_score: 0.0,
+            oldest_cell_pos: Vec2::ZERO,
+        }
+    }
+}
+
+impl DeathCause {
+    pub const ALL: [DeathCause; 7] = [
+        DeathCause::Starved,
+        DeathCause::Killed,
+        DeathCause::Idle,
+        DeathCause::Revolving,
+        DeathCause::OneDirection,
+        DeathCause::NoBullets,
+        DeathCause::Culled,
+    ];
+
+    pub fn label(&self) -> &str {
+        match self {
+            DeathCause::Starved => "starved",
+            DeathCause::Killed => "killed",
+            DeathCause::Idle => "idle",
+            DeathCause::Revolving => "revolving",
+            DeathCause::OneDirection => "one_direction",
+            DeathCause::NoBullets => "no_bullets",
+            DeathCause::Culled => "culled",
+        }
+    }
+}
+
+impl Demographics {
+    pub fn record_death(&mut self, cause: DeathCause) {
+        self.deaths[cause as usize] += 1;
+    }
+
+    pub fn deaths(&self, cause: DeathCause) -> u64 {
+        self.deaths[cause as usize]
+    }
+}
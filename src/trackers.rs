@@ -1,40 +1,185 @@
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use bevy::{prelude::*, time::common_conditions::on_timer};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::*;
 
 pub struct TrackersPlugin;
 
-pub struct InstantTracker(pub Instant);
+/// Simulated time, advanced by `SIM_TICK_SECS` on every `FixedUpdate` tick
+#[derive(Resource, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct SimClock {
+    ticks: u64,
+}
+
+/// Tick of the `SimClock` at which something last happened
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SimTracker(pub u64);
 
-#[derive(Default, Component)]
-pub struct LastUpdated(pub InstantTracker);
+#[derive(Component)]
+pub struct LastUpdated(pub SimTracker);
 #[derive(Component)]
 pub struct BirthPlace(pub Vec2);
-#[derive(Default, Component)]
-pub struct BirthTs(pub InstantTracker);
-#[derive(Default, Component)]
-pub struct LastBulletFired(pub InstantTracker);
+#[derive(Component)]
+pub struct BirthTs(pub SimTracker);
+#[derive(Component)]
+pub struct LastBulletFired(pub SimTracker);
 #[derive(Component)]
 pub struct NumCellsSpawned(pub u32);
 #[derive(Component)]
 pub struct PeriodicUpdateInterval(pub f32);
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct FitnessScores(Vec<f32>);
 
-#[derive(Default, Resource)]
-pub struct OneSecondTimer(pub InstantTracker);
+#[derive(Resource)]
+pub struct OneSecondTimer(pub SimTracker);
+
+/// Pause, single step and speed of the simulation
+#[derive(Resource)]
+pub struct TimeControl {
+    pub paused: bool,
+    /// Multiplier on the number of ticks run per real second
+    pub speed: f32,
+    step_requested: bool,
+}
+
+/// Ordering of the simulation systems within a `FixedUpdate` tick
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum SimSet {
+    /// Advances the `SimClock`
+    Clock,
+    /// Brings the spatial grids up to date with the previous tick's spawns, despawns and moves
+    Index,
+    /// Brains, energy, spawning and culling, runs before the physics step
+    Logic,
+    /// Reacts to the results of the physics step
+    PostPhysics,
+}
 
 impl Plugin for TrackersPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(OneSecondTimer::default()).add_systems(
-            Update,
-            update_second_timer.run_if(on_timer(Duration::from_secs_f32(1.0))),
-        );
+        app.insert_resource(FixedTime::new_from_secs(SIM_TICK_SECS))
+            .insert_resource(SimClock::default())
+            .insert_resource(OneSecondTimer(SimTracker(0)))
+            .insert_resource(TimeControl::default())
+            .configure_sets(
+                FixedUpdate,
+                (SimSet::Clock, SimSet::Index, SimSet::Logic).chain(),
+            )
+            .configure_set(FixedUpdate, SimSet::Clock.run_if(is_sim_running))
+            .configure_set(FixedUpdate, SimSet::Index.run_if(is_sim_running))
+            .configure_set(FixedUpdate, SimSet::Logic.run_if(is_sim_running))
+            .configure_set(FixedUpdate, SimSet::PostPhysics.run_if(is_sim_running))
+            .add_systems(Update, apply_sim_speed)
+            .add_systems(FixedUpdate, advance_sim_clock.in_set(SimSet::Clock))
+            .add_systems(FixedUpdate, finish_sim_step.in_set(SimSet::PostPhysics))
+            .add_systems(
+                FixedUpdate,
+                update_second_timer
+                    .run_if(on_sim_timer(1.0))
+                    .in_set(SimSet::Logic),
+            );
+    }
+}
+
+fn advance_sim_clock(mut clock: ResMut<SimClock>) {
+    clock.ticks += 1;
+}
+
+fn apply_sim_speed(time_control: Res<TimeControl>, mut fixed_time: ResMut<FixedTime>) {
+    if !time_control.is_changed() {
+        return;
+    }
+
+    fixed_time.period = Duration::from_secs_f32(SIM_TICK_SECS / time_control.speed);
+}
+
+/// A requested step only lasts for a single tick
+fn finish_sim_step(mut time_control: ResMut<TimeControl>) {
+    if time_control.step_requested {
+        time_control.step_requested = false;
+    }
+}
+
+/// Run condition for everything that advances the simulation
+pub fn is_sim_running(time_control: Res<TimeControl>) -> bool {
+    !time_control.paused || time_control.step_requested
+}
+
+fn update_second_timer(clock: Res<SimClock>, mut one_second_timer: ResMut<OneSecondTimer>) {
+    one_second_timer.0.set_now(&clock);
+}
+
+/// Run condition that's true once every `interval` seconds of simulated time, for `FixedUpdate`.
+/// It fires on whole multiples of the interval, so the phase is restored along with the
+/// `SimClock` when a snapshot is loaded, even an earlier one
+pub fn on_sim_timer(interval: f32) -> impl FnMut(Res<SimClock>) -> bool + Clone {
+    let interval_ticks = SimClock::ticks_for(interval);
+    move |clock: Res<SimClock>| clock.ticks > 0 && clock.ticks.is_multiple_of(interval_ticks)
+}
+
+impl SimClock {
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Total simulated seconds
+    pub fn elapsed(&self) -> f32 {
+        (self.ticks as f64 * SIM_TICK_SECS as f64) as f32
+    }
+
+    pub fn now(&self) -> SimTracker {
+        SimTracker(self.ticks)
+    }
+
+    /// Ticks it takes for `secs` of simulated time to pass, at least one
+    pub fn ticks_for(secs: f32) -> u64 {
+        let passed = |ticks| SimTracker(0).elapsed_past(&SimClock { ticks }, secs);
+        let mut ticks = ((secs as f64 / SIM_TICK_SECS as f64).ceil() as u64).max(1);
+        while !passed(ticks) {
+            ticks += 1;
+        }
+        while ticks > 1 && passed(ticks - 1) {
+            ticks -= 1;
+        }
+        ticks
     }
 }
 
-fn update_second_timer(mut one_second_timer: ResMut<OneSecondTimer>) {
-    one_second_timer.0.set_instant_now();
+impl TimeControl {
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Runs one more tick while paused
+    pub fn step(&mut self) {
+        if self.paused {
+            self.step_requested = true;
+        }
+    }
+
+    pub fn speed_up(&mut self) {
+        if let Some(speed) = SIM_SPEEDS.iter().find(|s| **s > self.speed) {
+            self.speed = *speed;
+        }
+    }
+
+    pub fn slow_down(&mut self) {
+        if let Some(speed) = SIM_SPEEDS.iter().rev().find(|s| **s < self.speed) {
+            self.speed = *speed;
+        }
+    }
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+            step_requested: false,
+        }
+    }
 }
 
 impl FitnessScores {
@@ -55,30 +200,25 @@ impl FitnessScores {
     }
 }
 
-impl InstantTracker {
-    pub fn elapsed(&self) -> f32 {
-        self.get_instant().elapsed().as_secs_f32()
+impl SimTracker {
+    /// Simulated seconds at the tracked tick
+    pub fn secs(&self) -> f32 {
+        (self.0 as f64 * SIM_TICK_SECS as f64) as f32
     }
 
-    pub fn elapsed_past(&self, interval: f32) -> bool {
-        self.get_instant().elapsed().as_secs_f32() >= interval
+    pub fn elapsed(&self, clock: &SimClock) -> f32 {
+        (clock.ticks.saturating_sub(self.0) as f64 * SIM_TICK_SECS as f64) as f32
     }
 
-    pub fn elapsed_within(&self, interval: f32) -> bool {
-        self.get_instant().elapsed().as_secs_f32() < interval
+    pub fn elapsed_past(&self, clock: &SimClock, interval: f32) -> bool {
+        self.elapsed(clock) >= interval
     }
 
-    pub fn get_instant(&self) -> Instant {
-        self.0
+    pub fn elapsed_within(&self, clock: &SimClock, interval: f32) -> bool {
+        self.elapsed(clock) < interval
     }
 
-    pub fn set_instant_now(&mut self) {
-        self.0 = Instant::now();
-    }
-}
-
-impl Default for InstantTracker {
-    fn default() -> Self {
-        Self(Instant::now())
+    pub fn set_now(&mut self, clock: &SimClock) {
+        self.0 = clock.ticks;
     }
 }
@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+
+use crate::{
+    boundary::{Boundary, BoundaryMode},
+    bullet::Bullet,
+    cell::{energy::Energy, focus::FocusedCell, user::UserControlledCell, Cell},
+    food::Food,
+    settings::SimSettings,
+    sim_config::SimConfig,
+    stats::SimStats,
+    trackers::{BirthTs, SimClock},
+    *,
+};
+
+/// Sprite handling, only added when the simulation is rendered
+pub struct VisualsPlugin;
+
+impl Plugin for VisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_boundary_outline)
+            .add_systems(Update, load_cell_sprites)
+            .add_systems(Update, load_food_sprites)
+            .add_systems(Update, load_bullet_sprites)
+            .add_systems(Update, update_cell_sprite)
+            .add_systems(Update, update_focused_cell_sprite);
+    }
+}
+
+/// Thin lines along the edges of the arena, colored by the boundary mode
+fn spawn_boundary_outline(mut commands: Commands, config: Res<SimConfig>) {
+    let boundary = Boundary::new(&config);
+    let color = match boundary.mode {
+        BoundaryMode::None => return,
+        BoundaryMode::Walls => Color::rgb(0.3, 0.3, 0.3),
+        BoundaryMode::Wrap => Color::rgba(0.2, 0.4, 0.9, 0.6),
+        BoundaryMode::Soft => Color::rgba(0.9, 0.5, 0.1, 0.6),
+    };
+
+    let Vec2 { x: hw, y: hh } = boundary.half_size;
+    let width = 10.0;
+    let edges = [
+        (Vec2::new(0.0, hh), Vec2::new(2.0 * hw + width, width)),
+        (Vec2::new(0.0, -hh), Vec2::new(2.0 * hw + width, width)),
+        (Vec2::new(hw, 0.0), Vec2::new(width, 2.0 * hh + width)),
+        (Vec2::new(-hw, 0.0), Vec2::new(width, 2.0 * hh + width)),
+    ];
+    for (pos, size) in edges {
+        commands.spawn(SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(size),
+                ..default()
+            },
+            transform: Transform::from_translation(pos.extend(-1.0)),
+            ..default()
+        });
+    }
+}
+
+fn load_cell_sprites(
+    asset_server: Res<AssetServer>,
+    mut cell_query: Query<(&mut Handle<Image>, Option<&UserControlledCell>), Added<Cell>>,
+) {
+    for (mut image_handle, user_cell) in cell_query.iter_mut() {
+        let sprite = match user_cell {
+            Some(_) => USER_CELL_SPRITE,
+            None => CELL_SPRITE,
+        };
+        *image_handle = asset_server.load(sprite);
+    }
+}
+
+fn load_food_sprites(
+    asset_server: Res<AssetServer>,
+    mut food_query: Query<&mut Handle<Image>, Added<Food>>,
+) {
+    for mut image_handle in food_query.iter_mut() {
+        *image_handle = asset_server.load(FOOD_SPRITE);
+    }
+}
+
+fn load_bullet_sprites(
+    asset_server: Res<AssetServer>,
+    mut bullet_query: Query<&mut Handle<Image>, Added<Bullet>>,
+) {
+    for mut image_handle in bullet_query.iter_mut() {
+        *image_handle = asset_server.load(BULLET_SPRITE);
+    }
+}
+
+fn update_cell_sprite(
+    clock: Res<SimClock>,
+    settings: Res<SimSettings>,
+    asset_server: Res<AssetServer>,
+    stats: Res<SimStats>,
+    mut cell_query: Query<(&Energy, &BirthTs, &Transform, &mut Handle<Image>), With<Cell>>,
+) {
+    if !settings.follow_best && !settings.follow_oldest {
+        return;
+    }
+
+    for (energy, birth_ts, transform, mut image_handle) in cell_query.iter_mut() {
+        if settings.follow_best
+            && transform
+                .translation
+                .truncate()
+                .distance_squared(stats.best_cell_pos)
+                <= 200.0
+        {
+            if energy.0 == stats.max_score {
+                *image_handle = asset_server.load(FOCUSED_CELL_SPRITE);
+            }
+        } else if settings.follow_oldest
+            && transform
+                .translation
+                .truncate()
+                .distance_squared(stats.oldest_cell_pos)
+                <= 200.0
+            && birth_ts.0.elapsed(&clock) as i32 == stats.max_age as i32
+        {
+            *image_handle = asset_server.load(FOCUSED_CELL_SPRITE);
+        } else {
+            *image_handle = asset_server.load(CELL_SPRITE);
+        }
+    }
+}
+
+fn update_focused_cell_sprite(
+    asset_server: Res<AssetServer>,
+    mut removed: RemovedComponents<FocusedCell>,
+    mut focused_cell_query: Query<&mut Handle<Image>, With<FocusedCell>>,
+    mut cell_query: Query<&mut Handle<Image>, (With<Cell>, Without<FocusedCell>)>,
+) {
+    for mut image_handle in focused_cell_query.iter_mut() {
+        *image_handle = asset_server.load(FOCUSED_CELL_SPRITE);
+    }
+    for entity in removed.iter() {
+        if let Ok(mut image_handle) = cell_query.get_mut(entity) {
+            *image_handle = asset_server.load(CELL_SPRITE);
+        }
+    }
+}
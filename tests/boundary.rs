@@ -0,0 +1,172 @@
+use ava::{
+    boundary::{Boundary, BoundaryMode},
+    spatial::SpatialGrid,
+};
+use bevy::{math::vec2, prelude::*};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+const TOLERANCE: f32 = 1e-2;
+
+fn arena(mode: BoundaryMode) -> Boundary {
+    Boundary {
+        mode,
+        half_size: vec2(400.0, 300.0),
+    }
+}
+
+fn random_inside(boundary: &Boundary, rng: &mut ChaCha8Rng) -> Vec2 {
+    let half = boundary.half_size;
+    vec2(
+        rng.gen_range(-half.x..half.x),
+        rng.gen_range(-half.y..half.y),
+    )
+}
+
+/// `pos` in each of the 9 copies of the arena around and including the original
+fn images(boundary: &Boundary, pos: Vec2) -> impl Iterator<Item = Vec2> {
+    let size = boundary.half_size * 2.0;
+    (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| pos + vec2(dx as f32, dy as f32) * size))
+}
+
+#[test]
+fn wrap_brings_positions_back_inside() {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let boundary = arena(BoundaryMode::Wrap);
+    let size = boundary.half_size * 2.0;
+    for _ in 0..1000 {
+        let pos = vec2(
+            rng.gen_range(-3000.0..3000.0),
+            rng.gen_range(-3000.0..3000.0),
+        );
+        let wrapped = boundary.wrap(pos);
+        assert!(wrapped.cmpge(-boundary.half_size).all());
+        assert!(wrapped.cmplt(boundary.half_size).all());
+
+        let num_sizes = (pos - wrapped) / size;
+        assert!((num_sizes - num_sizes.round()).abs().max_element() < 1e-4);
+    }
+
+    for mode in [BoundaryMode::None, BoundaryMode::Walls, BoundaryMode::Soft] {
+        let pos = vec2(2500.0, -1200.0);
+        assert_eq!(arena(mode).wrap(pos), pos);
+    }
+}
+
+#[test]
+fn offset_is_the_shortest_over_all_images() {
+    let mut rng = ChaCha8Rng::seed_from_u64(2);
+    let boundary = arena(BoundaryMode::Wrap);
+    for _ in 0..1000 {
+        let from = random_inside(&boundary, &mut rng);
+        let to = random_inside(&boundary, &mut rng);
+        let expected = images(&boundary, to)
+            .map(|image| image - from)
+            .min_by(|a, b| a.length().total_cmp(&b.length()))
+            .unwrap();
+        let offset = boundary.offset(from, to);
+        assert!(
+            (offset - expected).length() < TOLERANCE,
+            "from {} to {}: expected {}, found {}",
+            from,
+            to,
+            expected,
+            offset
+        );
+    }
+
+    let (from, to) = (vec2(-390.0, 0.0), vec2(390.0, 0.0));
+    assert_eq!(arena(BoundaryMode::Walls).offset(from, to), to - from);
+}
+
+#[test]
+fn distance_to_image_matches_the_closest_point() {
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+    let boundary = arena(BoundaryMode::Wrap);
+    let size = boundary.half_size * 2.0;
+    for _ in 0..200 {
+        let pos = random_inside(&boundary, &mut rng);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let shift = vec2(dx as f32, dy as f32) * size;
+                let (min, max) = (shift - boundary.half_size, shift + boundary.half_size);
+                let expected = pos.distance(pos.clamp(min, max));
+                let found = boundary.distance_to_image(pos, dx, dy);
+                assert!((found - expected).abs() < TOLERANCE);
+            }
+        }
+    }
+}
+
+#[test]
+fn nearest_matches_brute_force() {
+    let mut rng = ChaCha8Rng::seed_from_u64(4);
+    let boundary = arena(BoundaryMode::Wrap);
+    let mut grid = SpatialGrid::new(50.0);
+    let mut positions = Vec::new();
+    for i in 0..400 {
+        let pos = random_inside(&boundary, &mut rng);
+        grid.insert(Entity::from_raw(i), pos, i);
+        positions.push(pos);
+    }
+
+    let max_dist = 150.0;
+    for _ in 0..500 {
+        let query = random_inside(&boundary, &mut rng);
+        let expected = positions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % 3 == 0)
+            .flat_map(|(_, pos)| images(&boundary, *pos))
+            .map(|image| image.distance(query))
+            .filter(|d| *d <= max_dist)
+            .min_by(|a, b| a.total_cmp(b));
+
+        let found = boundary.nearest(&grid, query, max_dist, |e| e.item % 3 == 0);
+        match (found, expected) {
+            (Some((entry, image)), Some(expected)) => {
+                assert!((image.distance(query) - expected).abs() < TOLERANCE);
+                assert!(images(&boundary, entry.pos).any(|p| p.distance(image) < TOLERANCE));
+            }
+            (None, None) => {}
+            (found, expected) => panic!(
+                "at {}: expected {:?}, found {:?}",
+                query,
+                expected,
+                found.map(|(_, image)| image)
+            ),
+        }
+
+        // Without wrapping only the arena itself is searched
+        let walls = arena(BoundaryMode::Walls);
+        let found = walls.nearest(&grid, query, max_dist, |e| e.item % 3 == 0);
+        let direct = grid.nearest(query, max_dist, |e| e.item % 3 == 0);
+        assert_eq!(found.map(|(e, _)| e.entity), direct.map(|e| e.entity));
+    }
+}
+
+#[test]
+fn rays_leave_at_the_edge_and_come_back_opposite() {
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+    let boundary = arena(BoundaryMode::Wrap);
+    let size = boundary.half_size * 2.0;
+    for _ in 0..1000 {
+        let pos = random_inside(&boundary, &mut rng);
+        let angle: f32 = rng.gen_range(0.0..std::f32::consts::TAU);
+        let direction = vec2(angle.cos(), angle.sin());
+
+        let exit = pos + direction * boundary.edge_distance(pos, direction);
+        let overshoot = (exit.abs() - boundary.half_size).max_element();
+        assert!(overshoot.abs() < TOLERANCE, "{} isn't on an edge", exit);
+
+        let back_in = boundary.reenter(exit, direction);
+        // Moves a whole arena size along the crossed edges and not at all along the others
+        let jump = (back_in - exit).abs();
+        for (jump, size) in [(jump.x, size.x), (jump.y, size.y)] {
+            assert!(jump < TOLERANCE || (jump - size).abs() < TOLERANCE);
+        }
+        assert!((jump - size).abs().min_element() < TOLERANCE);
+        assert!(boundary.offset(exit, back_in).length() < TOLERANCE);
+        assert!(boundary.edge_distance(back_in, direction) > 0.0);
+    }
+}
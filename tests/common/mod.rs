@@ -0,0 +1,44 @@
+use ava::{
+    bullet::BulletPlugin, cell::CellPlugin, food::FoodPlugin, physics::PhysicsPlugin,
+    rng::RngPlugin, settings::SettingsPlugin, sim_config::SimConfig, stats::StatsPlugin,
+    trackers::TrackersPlugin,
+};
+use bevy::{hierarchy::HierarchyPlugin, prelude::*, transform::TransformPlugin};
+
+/// Set once the startup schedules ran
+#[derive(Resource)]
+struct Started;
+
+/// Windowless sim with every gameplay plugin, seeded from `config.seed`.
+/// More plugins and systems can be added until the first `step`
+pub fn sim_app(config: SimConfig) -> App {
+    let seed = config.seed;
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(TransformPlugin)
+        .add_plugins(HierarchyPlugin)
+        .insert_resource(config)
+        .add_plugins(SettingsPlugin)
+        .add_plugins(TrackersPlugin)
+        .add_plugins(RngPlugin { seed })
+        .add_plugins(PhysicsPlugin)
+        .add_plugins(BulletPlugin)
+        .add_plugins(FoodPlugin)
+        .add_plugins(CellPlugin)
+        .add_plugins(StatsPlugin);
+    app
+}
+
+/// Runs `num_ticks` fixed steps, after the startup schedules on the first call.
+/// The steps are driven by hand so that wall clock time plays no part
+pub fn step(app: &mut App, num_ticks: usize) {
+    if !app.world.contains_resource::<Started>() {
+        app.finish();
+        app.cleanup();
+        app.update();
+        app.insert_resource(Started);
+    }
+    for _ in 0..num_ticks {
+        app.world.run_schedule(FixedUpdate);
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use ava::{
+    cell::{energy::Energy, Cell},
+    food::Food,
+    sim_config::SimConfig,
+};
+use bevy::prelude::*;
+
+mod common;
+
+const NUM_TICKS: usize = 180;
+
+fn run(seed: u64) -> u64 {
+    // A smaller world keeps the test quick
+    let config = SimConfig {
+        seed: Some(seed),
+        num_cells: 500,
+        num_food: 1000,
+        ..default()
+    };
+
+    let mut app = common::sim_app(config);
+    common::step(&mut app, NUM_TICKS);
+
+    hash_world(&mut app.world)
+}
+
+fn hash_world(world: &mut World) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    let mut cells = world
+        .query::<(&Cell, &Transform)>()
+        .iter(world)
+        .map(|(c, t)| (c.0, t.translation, t.rotation))
+        .collect::<Vec<_>>();
+    cells.sort_by_key(|(id, _, _)| *id);
+    for (id, translation, rotation) in cells {
+        id.hash(&mut hasher);
+        translation.to_array().map(f32::to_bits).hash(&mut hasher);
+        rotation.to_array().map(f32::to_bits).hash(&mut hasher);
+    }
+
+    let mut energy = world
+        .query::<(&Cell, &Energy)>()
+        .iter(world)
+        .map(|(c, e)| (c.0, e.0.to_bits()))
+        .collect::<Vec<_>>();
+    energy.sort();
+    energy.hash(&mut hasher);
+
+    let mut food = world
+        .query_filtered::<&Transform, With<Food>>()
+        .iter(world)
+        .map(|t| t.translation.truncate().to_array().map(f32::to_bits))
+        .collect::<Vec<_>>();
+    food.sort();
+    food.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+#[test]
+fn same_seed_same_population() {
+    assert_eq!(run(7), run(7));
+}
+
+#[test]
+fn different_seed_different_population() {
+    assert_ne!(run(7), run(8));
+}
@@ -0,0 +1,45 @@
+use ava::{
+    cell::energy::{Energy, EnergyLedger, EnergyReason, PopulationLedger},
+    sim_config::SimConfig,
+};
+use bevy::prelude::*;
+
+mod common;
+
+#[test]
+fn ledger_accounts_for_every_energy_change() {
+    let config = SimConfig {
+        seed: Some(1),
+        num_cells: 100,
+        num_food: 300,
+        is_combat_enabled: true,
+        reproduction_cost: 5.0,
+        ..default()
+    };
+
+    let mut app = common::sim_app(config.clone());
+    common::step(&mut app, 60 * 15);
+
+    let mut cell_query = app.world.query::<(&Energy, &EnergyLedger)>();
+    for (energy, ledger) in cell_query.iter(&app.world) {
+        let net: f32 = EnergyReason::ALL.iter().map(|r| ledger.net(*r)).sum();
+        let expected = config.base_energy + net;
+        assert!(
+            (energy.0 - expected).abs() <= 1e-3 * expected.abs().max(1.0),
+            "energy {} doesn't match the ledger {}",
+            energy.0,
+            expected
+        );
+    }
+
+    let living: Vec<EnergyLedger> = cell_query.iter(&app.world).map(|(_, l)| *l).collect();
+    let total = app
+        .world
+        .resource::<PopulationLedger>()
+        .total(living.iter());
+    assert!(total.income(EnergyReason::Food) > 0.0);
+    assert!(total.expense(EnergyReason::Decay) > 0.0);
+    assert!(total.expense(EnergyReason::MissPenalty) > 0.0);
+    assert!(total.expense(EnergyReason::Reproduction) > 0.0);
+    assert_eq!(total.expense(EnergyReason::Food), 0.0);
+}
@@ -0,0 +1,71 @@
+use ava::{
+    events::{BulletExpired, BulletFired, CellBorn, CellDied, FoodEaten},
+    sim_config::SimConfig,
+    stats::{DeathCause, Demographics},
+    trackers::SimSet,
+};
+use bevy::prelude::*;
+
+mod common;
+
+#[derive(Resource, Default)]
+struct Log {
+    born: Vec<CellBorn>,
+    died: Vec<CellDied>,
+    fired: Vec<BulletFired>,
+    expired: Vec<BulletExpired>,
+    eaten: Vec<FoodEaten>,
+}
+
+fn collect(
+    mut log: ResMut<Log>,
+    mut born: EventReader<CellBorn>,
+    mut died: EventReader<CellDied>,
+    mut fired: EventReader<BulletFired>,
+    mut expired: EventReader<BulletExpired>,
+    mut eaten: EventReader<FoodEaten>,
+) {
+    log.born.extend(born.iter().copied());
+    log.died.extend(died.iter().copied());
+    log.fired.extend(fired.iter().copied());
+    log.expired.extend(expired.iter().copied());
+    log.eaten.extend(eaten.iter().copied());
+}
+
+#[test]
+fn events_match_the_simulation() {
+    let config = SimConfig {
+        seed: Some(1),
+        num_cells: 100,
+        num_food: 300,
+        ..default()
+    };
+
+    let mut app = common::sim_app(config.clone());
+    app.init_resource::<Log>()
+        // Last in the tick, so that no event of the final tick is missed
+        .add_systems(FixedUpdate, collect.after(SimSet::PostPhysics));
+    common::step(&mut app, 60 * 15);
+
+    let log = app.world.resource::<Log>();
+    let demographics = app.world.resource::<Demographics>();
+    assert_eq!(log.born.len() as u64, demographics.births);
+    assert_eq!(
+        log.born.iter().filter(|b| b.parent.is_none()).count(),
+        config.num_cells
+    );
+    let num_deaths: u64 = DeathCause::ALL
+        .iter()
+        .map(|cause| demographics.deaths(*cause))
+        .sum();
+    assert_eq!(log.died.len() as u64, num_deaths);
+
+    assert!(!log.fired.is_empty());
+    assert!(!log.expired.is_empty());
+    assert!(log.expired.iter().all(|e| e.energy_delta <= 0.0));
+    assert!(!log.eaten.is_empty());
+    assert!(log
+        .eaten
+        .iter()
+        .all(|e| e.energy_delta >= 0.0 && e.energy_delta <= config.energy_per_food));
+}
@@ -0,0 +1,58 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use ava::{
+    fitness::{Fitness, FitnessRegistry, RegisterFitness},
+    sim_config::SimConfig,
+    NUM_OUTPUT_NODES,
+};
+use bevy::prelude::*;
+
+mod common;
+
+struct Counting(Arc<AtomicUsize>);
+
+impl Fitness for Counting {
+    fn name(&self) -> &str {
+        "Counting"
+    }
+
+    fn score(&self, _: &[f64], _: &[f64; NUM_OUTPUT_NODES]) -> f32 {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        0.0
+    }
+}
+
+#[test]
+fn registry_replaces_and_falls_back() {
+    let mut registry = FitnessRegistry::default();
+    let num_builtin = registry.names().len();
+    assert!(registry.find("Survival").is_some());
+    assert_eq!(registry.get("Missing").name(), "Shaped");
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    registry.register(Counting(calls.clone()));
+    registry.register(Counting(calls));
+    assert_eq!(registry.names().len(), num_builtin + 1);
+    assert_eq!(registry.get("Counting").name(), "Counting");
+}
+
+#[test]
+fn registered_fitness_scores_the_cells() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let config = SimConfig {
+        seed: Some(1),
+        num_cells: 100,
+        num_food: 200,
+        fitness: "Counting".to_string(),
+        ..default()
+    };
+
+    let mut app = common::sim_app(config);
+    app.register_fitness(Counting(calls.clone()));
+    common::step(&mut app, 120);
+
+    assert!(calls.load(Ordering::Relaxed) > 0);
+}
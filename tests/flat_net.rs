@@ -0,0 +1,44 @@
+use ava::nn::{Activation, Net};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+const TOLERANCE: f64 = 1e-4;
+
+fn assert_matches(layer_sizes: Vec<usize>, activations: &[Activation], rng: &mut ChaCha8Rng) {
+    let net = Net::new(layer_sizes, activations, rng);
+    let flat = net.to_flat();
+    let mut values = vec![0.0; flat.num_values()];
+
+    for _ in 0..100 {
+        let inputs: Vec<f64> = (0..flat.num_inputs())
+            .map(|_| rng.gen_range(-1.0..1.0))
+            .collect();
+        let expected = net.predict(&inputs).concat();
+        flat.predict_into(&inputs, &mut values);
+
+        assert_eq!(expected.len(), values.len());
+        for (e, v) in expected.iter().zip(values.iter()) {
+            assert!(
+                (e - *v as f64).abs() < TOLERANCE,
+                "{:?}: expected {}, found {}",
+                activations,
+                e,
+                v
+            );
+        }
+    }
+}
+
+#[test]
+fn flat_net_matches_predict() {
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+    for activation in Activation::ALL {
+        assert_matches(vec![12, 8, 4], &[activation, activation], &mut rng);
+        assert_matches(vec![27, 16, 8, 6], &[activation; 3], &mut rng);
+    }
+    assert_matches(
+        vec![5, 3, 9, 4],
+        &[Activation::Tanh, Activation::LeakyRelu, Activation::Sigmoid],
+        &mut rng,
+    );
+}
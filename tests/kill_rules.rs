@@ -0,0 +1,118 @@
+use ava::{
+    cell::kill_rules::{CellVitals, KillRule, RegisterKillRule, Verdict},
+    events::CellDied,
+    sim_config::SimConfig,
+    stats::{DeathCause, Demographics},
+    trackers::SimSet,
+};
+use bevy::prelude::*;
+
+mod common;
+
+/// Culls every cell past its first second
+struct Cull;
+
+impl KillRule for Cull {
+    fn judge(&self, cell: &CellVitals) -> Verdict {
+        if cell.age >= 1.0 {
+            return Verdict::Kill(DeathCause::Culled);
+        }
+        Verdict::Spare
+    }
+}
+
+#[derive(Resource, Default)]
+struct Deaths(Vec<CellDied>);
+
+fn collect_deaths(mut deaths: ResMut<Deaths>, mut reader: EventReader<CellDied>) {
+    deaths.0.extend(reader.iter().copied());
+}
+
+fn new_app(config: SimConfig) -> App {
+    let mut app = common::sim_app(config);
+    app.init_resource::<Deaths>()
+        // Last in the tick, so that no event of the final tick is missed
+        .add_systems(FixedUpdate, collect_deaths.after(SimSet::PostPhysics));
+    app
+}
+
+#[test]
+fn custom_rule_culls_and_reports_deaths() {
+    let config = SimConfig {
+        seed: Some(1),
+        num_cells: 50,
+        num_food: 100,
+        ..default()
+    };
+    let mut app = new_app(config);
+    app.register_kill_rule(Cull);
+    common::step(&mut app, 120);
+
+    let deaths = &app.world.resource::<Deaths>().0;
+    assert!(deaths.iter().any(|d| d.cause == DeathCause::Culled));
+    assert!(deaths.iter().all(|d| d.age >= 1.0 || d.energy <= 0.0));
+
+    let demographics = app.world.resource::<Demographics>();
+    let num_culled = deaths
+        .iter()
+        .filter(|d| d.cause == DeathCause::Culled)
+        .count();
+    assert_eq!(demographics.deaths(DeathCause::Culled), num_culled as u64);
+}
+
+#[test]
+fn disabled_rules_spare_the_cells() {
+    let mut config = SimConfig {
+        seed: Some(1),
+        num_cells: 50,
+        num_food: 100,
+        ..default()
+    };
+    for key in ["idle", "revolving", "one_direction"] {
+        config
+            .set(&format!("kill_rules.{}.is_enabled=false", key))
+            .unwrap();
+    }
+    let mut app = new_app(config);
+    common::step(&mut app, 60 * 25);
+
+    let demographics = app.world.resource::<Demographics>();
+    for cause in [
+        DeathCause::Idle,
+        DeathCause::Revolving,
+        DeathCause::OneDirection,
+    ] {
+        assert_eq!(demographics.deaths(cause), 0);
+    }
+}
+
+#[test]
+fn drained_cells_die_of_the_drain() {
+    let mut config = SimConfig {
+        seed: Some(1),
+        num_cells: 50,
+        num_food: 100,
+        ..default()
+    };
+    for pair in [
+        "kill_rules.idle.is_enabled=false",
+        "kill_rules.revolving.is_enabled=false",
+        "kill_rules.one_direction.is_enabled=false",
+        "kill_rules.no_bullets.after_secs=0.0",
+        "kill_rules.no_bullets.penalty=1000.0",
+    ] {
+        config.set(pair).unwrap();
+    }
+    let mut app = new_app(config);
+    common::step(&mut app, 60);
+
+    let deaths = &app.world.resource::<Deaths>().0;
+    assert!(!deaths.is_empty());
+    assert!(deaths.iter().all(|d| d.cause == DeathCause::NoBullets));
+    let demographics = app.world.resource::<Demographics>();
+    assert_eq!(demographics.deaths(DeathCause::Starved), 0);
+    assert_eq!(
+        demographics.deaths(DeathCause::NoBullets),
+        deaths.len() as u64
+    );
+}
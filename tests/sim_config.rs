@@ -0,0 +1,183 @@
+use ava::{
+    boundary::BoundaryMode,
+    brain::BrainKind,
+    cell::MateSelection,
+    cli::CliArgs,
+    nn::Activation,
+    sim_config::{ConfigError, SimConfig},
+};
+
+/// Breaks a single field of a valid config
+type MakeInvalid = fn(&mut SimConfig);
+
+fn args(flags: &[&str]) -> Result<CliArgs, String> {
+    CliArgs::from_args(flags.iter().map(|f| f.to_string()))
+}
+
+#[test]
+fn overrides_set_fields_by_name() {
+    let mut config = SimConfig::default();
+    config.set("num_cells=12").unwrap();
+    config.set(" energy_per_food = 2.5 ").unwrap();
+    config.set("seed=Some(7)").unwrap();
+    config.set("hidden_layers=[6, 4]").unwrap();
+    assert_eq!(config.num_cells, 12);
+    assert_eq!(config.energy_per_food, 2.5);
+    assert_eq!(config.seed, Some(7));
+    assert_eq!(config.hidden_layers, vec![6, 4]);
+}
+
+#[test]
+fn overrides_reach_nested_fields() {
+    let mut config = SimConfig::default();
+    config.set("kill_rules.idle.is_enabled=false").unwrap();
+    config.set("kill_rules.no_bullets.penalty=3.0").unwrap();
+    assert!(!config.kill_rules.idle.is_enabled);
+    assert_eq!(config.kill_rules.no_bullets.penalty, 3.0);
+    assert!(config.kill_rules.revolving.is_enabled);
+}
+
+#[test]
+fn overrides_set_enums() {
+    let mut config = SimConfig::default();
+    config.set("boundary_mode=Wrap").unwrap();
+    config.set("mate_selection=Roulette").unwrap();
+    config.set("hidden_activations=[Relu, Tanh]").unwrap();
+    assert_eq!(config.boundary_mode, BoundaryMode::Wrap);
+    assert_eq!(config.mate_selection, MateSelection::Roulette);
+    assert_eq!(
+        config.hidden_activations,
+        vec![Activation::Relu, Activation::Tanh]
+    );
+}
+
+#[test]
+fn bad_overrides_are_rejected() {
+    for pair in [
+        "num_cells",
+        "no_such_key=1",
+        "kill_rules.idle.no_such_key=1",
+        "num_cells.nested=1",
+        "num_cells=\"many\"",
+        "num_cells=-1",
+        "is_combat_enabled=1",
+        "boundary_mode=Sideways",
+        "hidden_layers=[6,",
+    ] {
+        let mut config = SimConfig::default();
+        assert!(
+            matches!(config.set(pair), Err(ConfigError::Override(_))),
+            "{} was accepted",
+            pair
+        );
+        assert_eq!(config.num_cells, SimConfig::default().num_cells);
+    }
+}
+
+#[test]
+fn every_check_rejects_its_bad_value() {
+    let cases: Vec<(&str, MakeInvalid)> = vec![
+        ("world_width and world_height", |c| c.world_width = 0),
+        ("num_cells", |c| c.num_cells = 0),
+        ("cell_speed", |c| c.cell_speed = -1.0),
+        ("base_energy", |c| c.base_energy = 0.0),
+        ("max_energy", |c| c.max_energy = c.base_energy - 1.0),
+        ("energy_decay_rate", |c| c.energy_decay_rate = -0.1),
+        ("reproduction_cost", |c| c.reproduction_cost = -1.0),
+        ("update_interval", |c| c.update_interval = 0.0),
+        ("vision_radius", |c| c.vision_radius = 0.0),
+        ("cell_size", |c| c.cell_size = 0.0),
+        ("trait_mutation_rate", |c| c.trait_mutation_rate = 1.5),
+        ("trait_mutation_variation", |c| {
+            c.trait_mutation_variation = 1.0
+        }),
+        ("trait_energy_cost", |c| c.trait_energy_cost = -1.0),
+        ("bullet_lifespan", |c| c.bullet_lifespan = 0.0),
+        ("bullet_speed", |c| c.bullet_speed = 0.0),
+        ("bullet_fire_rate", |c| c.bullet_fire_rate = -1.0),
+        ("bullet_damage", |c| c.bullet_damage = -1.0),
+        ("kill_bounty", |c| c.kill_bounty = -1.0),
+        ("kill_rules.no_bullets.penalty", |c| {
+            c.kill_rules.no_bullets.penalty = -1.0
+        }),
+        ("energy_per_food", |c| c.energy_per_food = -1.0),
+        ("num_vision_rays", |c| {
+            c.is_vision_enabled = true;
+            c.num_vision_rays = 0;
+        }),
+        ("vision_fov_degrees", |c| c.vision_fov_degrees = 400.0),
+        ("hidden_layers", |c| c.hidden_layers = vec![4, 0]),
+        ("hidden_activations", |c| {
+            c.hidden_layers = vec![4, 4, 4];
+            c.hidden_activations = vec![Activation::Relu, Activation::Tanh];
+        }),
+        ("activation_mutation_rate", |c| {
+            c.activation_mutation_rate = -0.1
+        }),
+        ("hidden_activations, output_activation", |c| {
+            c.brain_kind = BrainKind::Neat;
+            c.output_activation = Activation::Tanh;
+        }),
+        ("brain_mutation_rate", |c| c.brain_mutation_rate = 2.0),
+        ("brain_mutation_variation", |c| {
+            c.brain_mutation_variation = 0.0
+        }),
+        ("neat_add_node_rate", |c| c.neat_add_connection_rate = 1.5),
+        ("neat_compatibility_threshold", |c| {
+            c.neat_compatibility_threshold = 0.0
+        }),
+        ("stats_interval", |c| c.stats_interval = 0.0),
+    ];
+
+    assert!(SimConfig::default().validate().is_ok());
+    let neat = SimConfig {
+        brain_kind: BrainKind::Neat,
+        ..SimConfig::default()
+    };
+    assert!(neat.validate().is_ok());
+    for (field, make_invalid) in cases {
+        let mut config = SimConfig::default();
+        make_invalid(&mut config);
+        match config.validate() {
+            Err(ConfigError::Invalid(msg)) => {
+                assert!(msg.starts_with(field), "bad {} reported as: {}", field, msg)
+            }
+            _ => panic!("bad {} was accepted", field),
+        }
+    }
+}
+
+#[test]
+fn command_line_overrides_apply_in_order() {
+    let args = args(&[
+        "--set",
+        "num_cells=10",
+        "--seed",
+        "5",
+        "--set",
+        "kill_rules.revolving.is_enabled=false",
+        "--record",
+        "out/stats.csv",
+        "--set",
+        "num_cells=20",
+        "--resume",
+        "snapshot.json",
+    ])
+    .unwrap();
+    assert_eq!(args.config.num_cells, 20);
+    assert_eq!(args.config.seed, Some(5));
+    assert!(!args.config.kill_rules.revolving.is_enabled);
+    assert_eq!(args.config.stats_file.as_deref(), Some("out/stats.csv"));
+    assert_eq!(args.resume_from.as_deref(), Some("snapshot.json"));
+}
+
+#[test]
+fn command_line_errors_are_reported() {
+    assert!(args(&["--frobnicate"]).is_err());
+    assert!(args(&["--set"]).is_err());
+    assert!(args(&["--set", "no_such_key=1"]).is_err());
+    assert!(args(&["--set", "num_cells=\"many\""]).is_err());
+    assert!(args(&["--config", "no/such/config.ron"]).is_err());
+    let err = args(&["--set", "num_cells=0"]).err().unwrap();
+    assert!(err.contains("num_cells must be positive"), "{}", err);
+}
@@ -0,0 +1,126 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use ava::{
+    cell::{energy::Energy, Cell},
+    food::Food,
+    sim_config::SimConfig,
+    snapshot::{LoadSnapshotEvent, SaveSnapshotEvent, SnapshotPlugin},
+    stats::{DeathCause, Demographics},
+    trackers::SimClock,
+};
+use bevy::prelude::*;
+
+mod common;
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir()
+        .join(format!("ava-snapshot-{}", std::process::id()))
+        .join(name)
+}
+
+fn snapshot_app(config: SimConfig) -> App {
+    let mut app = common::sim_app(config);
+    app.add_plugins(SnapshotPlugin { resume_from: None });
+    app
+}
+
+/// Snapshots are saved and loaded by `Update` systems
+fn save(app: &mut App, path: &Path) {
+    app.world
+        .send_event(SaveSnapshotEvent(path.to_string_lossy().into()));
+    app.world.run_schedule(Update);
+}
+
+fn load(app: &mut App, path: &Path) {
+    app.world
+        .send_event(LoadSnapshotEvent(path.to_string_lossy().into()));
+    app.world.run_schedule(Update);
+}
+
+fn births_and_deaths(app: &App) -> (u64, u64) {
+    let demographics = app.world.resource::<Demographics>();
+    let deaths = DeathCause::ALL
+        .iter()
+        .map(|c| demographics.deaths(*c))
+        .sum();
+    (demographics.births, deaths)
+}
+
+/// Id, position and energy of every cell
+fn cells(world: &mut World) -> Vec<(u32, Vec2, f32)> {
+    let mut cells = world
+        .query::<(&Cell, &Transform, &Energy)>()
+        .iter(world)
+        .map(|(c, t, e)| (c.0, t.translation.truncate(), e.0))
+        .collect::<Vec<_>>();
+    cells.sort_by_key(|(id, ..)| *id);
+    cells
+}
+
+fn food(world: &mut World) -> Vec<[u32; 2]> {
+    let mut food = world
+        .query_filtered::<&Transform, With<Food>>()
+        .iter(world)
+        .map(|t| t.translation.truncate().to_array().map(f32::to_bits))
+        .collect::<Vec<_>>();
+    food.sort();
+    food
+}
+
+#[test]
+fn resumed_run_matches_the_original() {
+    let config = SimConfig {
+        seed: Some(2),
+        num_cells: 300,
+        num_food: 600,
+        ..default()
+    };
+    let path = temp_path("resume.json");
+    let mut original = snapshot_app(config.clone());
+    // Right before the 5 second timers, so that the resumed run has to pick up their phase
+    // and the restored cells can be culled on the very first tick
+    common::step(&mut original, 599);
+    save(&mut original, &path);
+    common::step(&mut original, 600);
+
+    let mut resumed = snapshot_app(config);
+    common::step(&mut resumed, 0);
+    load(&mut resumed, &path);
+    common::step(&mut resumed, 600);
+
+    let original_cells = cells(&mut original.world);
+    let resumed_cells = cells(&mut resumed.world);
+    assert_eq!(original_cells.len(), resumed_cells.len());
+    assert_eq!(original_cells, resumed_cells);
+    assert_eq!(food(&mut original.world), food(&mut resumed.world));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn loading_an_earlier_snapshot_keeps_the_sim_going() {
+    let config = SimConfig {
+        seed: Some(1),
+        num_cells: 300,
+        num_food: 600,
+        ..default()
+    };
+    let path = temp_path("rewind.json");
+    let mut app = snapshot_app(config);
+    common::step(&mut app, 600);
+    save(&mut app, &path);
+    common::step(&mut app, 1800);
+
+    load(&mut app, &path);
+    assert_eq!(app.world.resource::<SimClock>().ticks(), 600);
+    let (births, deaths) = births_and_deaths(&app);
+    common::step(&mut app, 600);
+    let (births_after, deaths_after) = births_and_deaths(&app);
+    assert!(births_after > births, "no births after the rewind");
+    assert!(deaths_after > deaths, "no deaths after the rewind");
+
+    fs::remove_file(path).unwrap();
+}
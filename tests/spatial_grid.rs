@@ -0,0 +1,77 @@
+use ava::spatial::SpatialGrid;
+use bevy::{prelude::*, utils::HashMap};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+fn random_pos(rng: &mut ChaCha8Rng) -> Vec2 {
+    Vec2::new(
+        rng.gen_range(-3000.0..3000.0),
+        rng.gen_range(-3000.0..3000.0),
+    )
+}
+
+#[test]
+fn grid_matches_brute_force() {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let mut grid = SpatialGrid::new(100.0);
+    let mut positions = HashMap::new();
+    for i in 0..3000 {
+        let (entity, pos) = (Entity::from_raw(i), random_pos(&mut rng));
+        grid.insert(entity, pos, i);
+        positions.insert(entity, pos);
+    }
+    // Despawn some and move others
+    for i in 0..1000 {
+        let entity = Entity::from_raw(i);
+        if i % 2 == 0 {
+            grid.remove(entity);
+            positions.remove(&entity);
+        } else {
+            let pos = random_pos(&mut rng);
+            grid.insert(entity, pos, i);
+            positions.insert(entity, pos);
+        }
+    }
+    assert_eq!(grid.len(), positions.len());
+
+    for _ in 0..500 {
+        let query = random_pos(&mut rng) * 1.3;
+        let mut expected: Vec<(f32, Entity)> = positions
+            .iter()
+            .map(|(e, p)| (p.distance_squared(query), *e))
+            .collect();
+        expected.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let found = grid.k_nearest(query, 5, f32::INFINITY, |_| true);
+        let found: Vec<f32> = found
+            .iter()
+            .map(|e| e.pos.distance_squared(query))
+            .collect();
+        let closest: Vec<f32> = expected.iter().take(5).map(|(d, _)| *d).collect();
+        assert_eq!(found, closest);
+
+        let nearest = grid.nearest(query, 150.0, |e| e.item % 3 == 0);
+        let closest = expected
+            .iter()
+            .find(|(d, e)| *d <= 150.0 * 150.0 && e.index() % 3 == 0);
+        assert_eq!(nearest.map(|e| e.entity), closest.map(|(_, e)| *e));
+
+        let num_within = expected.iter().filter(|(d, _)| *d <= 250.0 * 250.0).count();
+        assert_eq!(grid.within_radius(query, 250.0).len(), num_within);
+    }
+}
+
+#[test]
+fn prune_drops_unseen_despawns() {
+    let mut grid = SpatialGrid::new(50.0);
+    for i in 0..20 {
+        grid.insert(Entity::from_raw(i), Vec2::splat(i as f32 * 10.0), ());
+    }
+
+    grid.prune(10, |e| e.index() < 10);
+    assert_eq!(grid.len(), 10);
+    assert!(grid
+        .within_radius(Vec2::ZERO, 1000.0)
+        .iter()
+        .all(|e| e.entity.index() < 10));
+}